@@ -4,6 +4,8 @@ use std::path::PathBuf;
 
 use crate::config;
 use crate::config::AppListConfig;
+use crate::dbus_subscription::dbus_subscription;
+use crate::dbus_subscription::DbusUpdate;
 use crate::fl;
 use crate::toplevel_subscription::toplevel_subscription;
 use crate::toplevel_subscription::ToplevelRequest;
@@ -36,6 +38,7 @@ use cosmic::widget::rectangle_tracker::rectangle_tracker_subscription;
 use cosmic::widget::rectangle_tracker::RectangleTracker;
 use cosmic::widget::rectangle_tracker::RectangleUpdate;
 use cosmic::{Element, Theme};
+use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::State as ToplevelState;
 use cosmic_protocols::toplevel_info::v1::client::zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1;
 use freedesktop_desktop_entry::DesktopEntry;
 use iced::widget::container;
@@ -44,6 +47,10 @@ use iced::Alignment;
 use iced::Background;
 use iced::Length;
 use itertools::Itertools;
+use log::error;
+
+// maximum number of dots shown for the windows of an app
+const MAX_DOTS: usize = 3;
 
 pub fn run() -> cosmic::iced::Result {
     let helper = CosmicAppletHelper::default();
@@ -114,6 +121,7 @@ enum Message {
     NewSeat(WlSeat),
     RemovedSeat(WlSeat),
     Rectangle(RectangleUpdate<u32>),
+    Dbus(DbusUpdate),
 }
 
 #[derive(Debug, Clone, Default)]
@@ -162,6 +170,34 @@ fn desktop_info_for_app_ids(mut app_ids: Vec<String>) -> Vec<DesktopInfo> {
     ret
 }
 
+impl CosmicAppList {
+    /// favorite and other app groups, as they are shown in the dock
+    fn dock_groups(&self) -> (Vec<&Toplevel>, Vec<&Toplevel>) {
+        self.toplevel_list.iter().partition(|t| {
+            self.config.favorites.contains(&t.desktop_info.id)
+                || self.config.favorites.contains(&t.desktop_info.name)
+        })
+    }
+
+    /// app groups in the order they are shown in the dock
+    fn dock_order(&self) -> Vec<&Toplevel> {
+        let (favorites, running) = self.dock_groups();
+        favorites.into_iter().chain(running).collect()
+    }
+
+    fn activate_index(&mut self, i: usize) -> Command<Message> {
+        let message = match self.dock_order().get(i) {
+            Some(toplevel_group) => toplevel_group
+                .toplevels
+                .first()
+                .map(|t| Message::Activate(t.0.clone()))
+                .unwrap_or_else(|| Message::Exec(toplevel_group.desktop_info.exec.clone())),
+            None => return Command::none(),
+        };
+        self.update(message)
+    }
+}
+
 impl Application for CosmicAppList {
     type Message = Message;
     type Theme = Theme;
@@ -198,8 +234,8 @@ impl Application for CosmicAppList {
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::Errored(_) => {
-                // TODO log errors
+            Message::Errored(err) => {
+                error!("{}", err);
             }
             Message::Popup(id) => {
                 if let Some(toplevel_group) = self
@@ -365,6 +401,23 @@ impl Application for CosmicAppList {
                     self.rectangle_tracker.replace(tracker);
                 }
             },
+            Message::Dbus(event) => match event {
+                DbusUpdate::ActivateIndex(i) => {
+                    return self.activate_index(i as usize);
+                }
+                DbusUpdate::LaunchIndex(i) => {
+                    if let Some(exec) = self
+                        .dock_order()
+                        .get(i as usize)
+                        .map(|t| t.desktop_info.exec.clone())
+                    {
+                        return self.update(Message::Exec(exec));
+                    }
+                }
+                DbusUpdate::Error(err) => {
+                    return self.update(Message::Errored(err));
+                }
+            },
             Message::Ignore => {}
             Message::ClosePopup => {
                 if let Some(p) = self.popup.take() {
@@ -384,24 +437,50 @@ impl Application for CosmicAppList {
         match id {
             SurfaceIdWrapper::LayerSurface(_) => unimplemented!(),
             SurfaceIdWrapper::Window(_) => {
-                let (favorites, running) = self.toplevel_list.iter().fold(
-                    (Vec::new(), Vec::new()),
-                    |(mut favorites, mut running),
-                     Toplevel {
-                         id,
-                         toplevels,
-                         desktop_info,
-                         ..
-                     }| {
-                        let cosmic_icon = cosmic::widget::icon(
-                            Path::new(&desktop_info.icon),
-                            self.applet_helper.suggested_size().0,
-                        );
+                let app_button = |Toplevel {
+                                      id,
+                                      toplevels,
+                                      desktop_info,
+                                      ..
+                                  }: &Toplevel|
+                 -> Element<Message> {
+                    let cosmic_icon = cosmic::widget::icon(
+                        Path::new(&desktop_info.icon),
+                        self.applet_helper.suggested_size().0,
+                    );
 
-                        let dot_radius = 2;
-                        let dots = (0..toplevels.len())
-                            .into_iter()
-                            .map(|_| {
+                    let dot_radius = 2;
+                    let is_active = toplevels
+                        .iter()
+                        .any(|(_, info)| info.state.contains(&ToplevelState::Activated));
+                    let dots = (0..toplevels.len().min(MAX_DOTS))
+                        .into_iter()
+                        .map(|i| {
+                            if i == 0 && is_active {
+                                // the active indicator is stretched along the panel
+                                let indicator = match &self.applet_helper.anchor {
+                                    PanelAnchor::Left | PanelAnchor::Right => {
+                                        vertical_space(Length::Units(dot_radius * 4))
+                                    }
+                                    PanelAnchor::Top | PanelAnchor::Bottom => {
+                                        horizontal_space(Length::Units(dot_radius * 4))
+                                    }
+                                };
+                                container(indicator)
+                                    .padding(dot_radius)
+                                    .style(<Self::Theme as container::StyleSheet>::Style::Custom(
+                                        |theme| container::Appearance {
+                                            text_color: Some(Color::TRANSPARENT),
+                                            background: Some(Background::Color(
+                                                theme.cosmic().accent_color().into(),
+                                            )),
+                                            border_radius: 4.0,
+                                            border_width: 0.0,
+                                            border_color: Color::TRANSPARENT,
+                                        },
+                                    ))
+                                    .into()
+                            } else {
                                 container(vertical_space(Length::Units(0)))
                                     .padding(dot_radius)
                                     .style(<Self::Theme as container::StyleSheet>::Style::Custom(
@@ -416,66 +495,60 @@ impl Application for CosmicAppList {
                                         },
                                     ))
                                     .into()
-                            })
-                            .collect_vec();
-                        let icon_wrapper = match &self.applet_helper.anchor {
-                            PanelAnchor::Left => {
-                                row(vec![column(dots).spacing(4).into(), cosmic_icon.into()])
-                                    .align_items(iced::Alignment::Center)
-                                    .spacing(4)
-                                    .into()
-                            }
-                            PanelAnchor::Right => {
-                                row(vec![cosmic_icon.into(), column(dots).spacing(4).into()])
-                                    .align_items(iced::Alignment::Center)
-                                    .spacing(4)
-                                    .into()
-                            }
-                            PanelAnchor::Top => {
-                                column(vec![row(dots).spacing(4).into(), cosmic_icon.into()])
-                                    .align_items(iced::Alignment::Center)
-                                    .spacing(4)
-                                    .into()
-                            }
-                            PanelAnchor::Bottom => {
-                                column(vec![cosmic_icon.into(), row(dots).spacing(4).into()])
-                                    .align_items(iced::Alignment::Center)
-                                    .spacing(4)
-                                    .into()
                             }
-                        };
-                        let mut icon_button = cosmic::widget::button(Button::Text)
-                            .custom(vec![icon_wrapper])
-                            .padding(8);
-                        if self.popup.is_none() {
-                            icon_button = icon_button.on_press(
-                                toplevels
-                                    .first()
-                                    .map(|t| Message::Activate(t.0.clone()))
-                                    .unwrap_or_else(|| Message::Exec(desktop_info.exec.clone())),
-                            );
+                        })
+                        .collect_vec();
+                    let icon_wrapper = match &self.applet_helper.anchor {
+                        PanelAnchor::Left => {
+                            row(vec![column(dots).spacing(4).into(), cosmic_icon.into()])
+                                .align_items(iced::Alignment::Center)
+                                .spacing(4)
+                                .into()
                         }
-
-                        // TODO tooltip on hover
-                        let icon_button = mouse_listener(
-                            icon_button.width(Length::Shrink).height(Length::Shrink),
-                        )
-                        .on_right_release(Message::Popup(desktop_info.id.clone()));
-                        let icon_button = if let Some(tracker) = self.rectangle_tracker.as_ref() {
-                            tracker.container(*id, icon_button).into()
-                        } else {
-                            icon_button.into()
-                        };
-                        if self.config.favorites.contains(&desktop_info.id)
-                            || self.config.favorites.contains(&desktop_info.name)
-                        {
-                            favorites.push(icon_button)
-                        } else {
-                            running.push(icon_button);
+                        PanelAnchor::Right => {
+                            row(vec![cosmic_icon.into(), column(dots).spacing(4).into()])
+                                .align_items(iced::Alignment::Center)
+                                .spacing(4)
+                                .into()
                         }
-                        (favorites, running)
-                    },
-                );
+                        PanelAnchor::Top => {
+                            column(vec![row(dots).spacing(4).into(), cosmic_icon.into()])
+                                .align_items(iced::Alignment::Center)
+                                .spacing(4)
+                                .into()
+                        }
+                        PanelAnchor::Bottom => {
+                            column(vec![cosmic_icon.into(), row(dots).spacing(4).into()])
+                                .align_items(iced::Alignment::Center)
+                                .spacing(4)
+                                .into()
+                        }
+                    };
+                    let mut icon_button = cosmic::widget::button(Button::Text)
+                        .custom(vec![icon_wrapper])
+                        .padding(8);
+                    if self.popup.is_none() {
+                        icon_button = icon_button.on_press(
+                            toplevels
+                                .first()
+                                .map(|t| Message::Activate(t.0.clone()))
+                                .unwrap_or_else(|| Message::Exec(desktop_info.exec.clone())),
+                        );
+                    }
+
+                    // TODO tooltip on hover
+                    let icon_button =
+                        mouse_listener(icon_button.width(Length::Shrink).height(Length::Shrink))
+                            .on_right_release(Message::Popup(desktop_info.id.clone()));
+                    if let Some(tracker) = self.rectangle_tracker.as_ref() {
+                        tracker.container(*id, icon_button).into()
+                    } else {
+                        icon_button.into()
+                    }
+                };
+                let (favorites, running) = self.dock_groups();
+                let favorites = favorites.into_iter().map(app_button).collect_vec();
+                let running = running.into_iter().map(app_button).collect_vec();
 
                 let (w, h) = match self.applet_helper.anchor {
                     PanelAnchor::Top | PanelAnchor::Bottom => (Length::Shrink, Length::Fill),
@@ -607,6 +680,7 @@ impl Application for CosmicAppList {
                 _ => None,
             }),
             rectangle_tracker_subscription(0).map(|(_, update)| Message::Rectangle(update)),
+            dbus_subscription(0).map(|(_, event)| Message::Dbus(event)),
         ])
     }
 
@@ -0,0 +1,144 @@
+use crate::config::APP_ID;
+use cosmic::iced;
+use cosmic::iced::subscription;
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+use std::{env, fmt::Debug, hash::Hash, process};
+use zbus::{
+    dbus_interface,
+    fdo::{DBusProxy, RequestNameFlags, RequestNameReply},
+    names::WellKnownName,
+    Connection, ConnectionBuilder,
+};
+
+pub const OBJECT_PATH: &str = "/com/system76/CosmicAppList";
+
+/// The bus name of this instance, `APP_ID` followed by the panel and output
+/// it is shown on, e.g. `com.system76.CosmicAppList.Dock.DP_1`
+///
+/// `APP_ID` itself is only owned by the first instance to start.
+fn instance_name() -> String {
+    let elements: Vec<String> = ["COSMIC_PANEL_NAME", "COSMIC_PANEL_OUTPUT"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .filter(|value| !value.is_empty())
+        .map(|value| bus_name_element(&value))
+        .collect();
+    if elements.is_empty() {
+        format!("{}.Pid{}", APP_ID, process::id())
+    } else {
+        format!("{}.{}", APP_ID, elements.join("."))
+    }
+}
+
+// elements of a bus name may only contain [A-Za-z0-9_] and not start with a digit
+fn bus_name_element(value: &str) -> String {
+    let mut element: String = value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if element.starts_with(|c: char| c.is_ascii_digit()) {
+        element.insert(0, '_');
+    }
+    element
+}
+
+pub fn dbus_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
+    id: I,
+) -> iced::Subscription<(I, DbusUpdate)> {
+    subscription::unfold(id, State::Ready, move |state| start_listening(id, state))
+}
+
+pub enum State {
+    Ready,
+    Waiting(Connection, UnboundedReceiver<DbusUpdate>),
+    Finished,
+}
+
+async fn start_listening<I: Copy>(id: I, state: State) -> (Option<(I, DbusUpdate)>, State) {
+    match state {
+        State::Ready => {
+            let (tx, rx) = unbounded();
+            let conn = match ConnectionBuilder::session()
+                .and_then(|builder| builder.serve_at(OBJECT_PATH, AppListServer { tx: tx.clone() }))
+            {
+                Ok(builder) => builder.build().await,
+                Err(e) => Err(e),
+            };
+            let conn = match conn {
+                Ok(conn) => conn,
+                Err(e) => {
+                    return (
+                        Some((id, DbusUpdate::Error(e.to_string()))),
+                        State::Finished,
+                    )
+                }
+            };
+            if let Err(e) = request_names(&conn).await {
+                let _ = tx.unbounded_send(DbusUpdate::Error(e));
+            }
+            (None, State::Waiting(conn, rx))
+        }
+        State::Waiting(conn, mut rx) => match rx.next().await {
+            Some(u) => (Some((id, u)), State::Waiting(conn, rx)),
+            None => (None, State::Finished),
+        },
+        State::Finished => iced::futures::future::pending().await,
+    }
+}
+
+// claim the instance name and, if no other app list has it, `APP_ID`
+async fn request_names(conn: &Connection) -> Result<(), String> {
+    let dbus = DBusProxy::new(conn).await.map_err(|e| e.to_string())?;
+    let instance = instance_name();
+    if !request_name(&dbus, &instance).await? {
+        return Err(format!("{} is already owned by another app list", instance));
+    }
+    if !request_name(&dbus, APP_ID).await? {
+        return Err(format!(
+            "{} is already owned by another app list, this one is only reachable as {}",
+            APP_ID, instance
+        ));
+    }
+    Ok(())
+}
+
+async fn request_name(dbus: &DBusProxy<'_>, name: &str) -> Result<bool, String> {
+    let name = WellKnownName::try_from(name).map_err(|e| e.to_string())?;
+    match dbus
+        .request_name(name, RequestNameFlags::DoNotQueue.into())
+        .await
+    {
+        Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner) => Ok(true),
+        Ok(_) => Ok(false),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Requests received on the `com.system76.CosmicAppList` interface
+/// indices are 0-based and follow the order of the dock, favorites first
+#[derive(Clone, Debug)]
+pub enum DbusUpdate {
+    ActivateIndex(u32),
+    LaunchIndex(u32),
+    Error(String),
+}
+
+struct AppListServer {
+    tx: UnboundedSender<DbusUpdate>,
+}
+
+#[dbus_interface(name = "com.system76.CosmicAppList")]
+impl AppListServer {
+    /// Activate the nth app, launching it if it has no windows
+    fn activate_index(&self, index: u32) {
+        let _ = self.tx.unbounded_send(DbusUpdate::ActivateIndex(index));
+    }
+
+    /// Launch a new instance of the nth app
+    fn launch_index(&self, index: u32) {
+        let _ = self.tx.unbounded_send(DbusUpdate::LaunchIndex(index));
+    }
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0-only
 mod app;
 mod config;
+mod dbus_subscription;
 mod localize;
 mod toplevel_handler;
 mod toplevel_subscription;
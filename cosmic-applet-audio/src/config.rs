@@ -0,0 +1,65 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::PathBuf;
+use xdg::BaseDirectories;
+
+pub const APP_ID: &str = "com.system76.CosmicAppletAudio";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Volume change in percent for each step of the mouse wheel
+    pub volume_step: f64,
+    /// Allow the output volume to go up to 150%
+    pub allow_overdrive: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            volume_step: 5.0,
+            allow_overdrive: false,
+        }
+    }
+}
+
+impl AudioConfig {
+    /// load the config, falling back to the defaults
+    pub fn load() -> anyhow::Result<AudioConfig> {
+        let mut relative_path = PathBuf::from(APP_ID);
+        relative_path.push("config.ron");
+        let file = match BaseDirectories::new()
+            .ok()
+            .and_then(|dirs| dirs.find_config_file(relative_path))
+            .and_then(|p| File::open(p).ok())
+        {
+            Some(path) => path,
+            _ => {
+                anyhow::bail!("Failed to load config");
+            }
+        };
+
+        ron::de::from_reader::<_, AudioConfig>(file)
+            .map_err(|err| anyhow!("Failed to parse config file: {}", err))
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let bd = BaseDirectories::new()?;
+        let mut relative_path = PathBuf::from(APP_ID);
+        relative_path.push("config.ron");
+        let config_path = bd.place_config_file(relative_path)?;
+        let f = File::create(config_path)?;
+        ron::ser::to_writer_pretty(f, self, Default::default())?;
+        Ok(())
+    }
+
+    /// Highest output volume in percent
+    pub fn max_volume(&self) -> f64 {
+        if self.allow_overdrive {
+            150.0
+        } else {
+            100.0
+        }
+    }
+}
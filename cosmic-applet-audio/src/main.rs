@@ -1,10 +1,11 @@
-use cosmic::iced::wayland::SurfaceIdWrapper;
-use cosmic::iced::widget;
+use cosmic::iced::mouse::{self, ScrollDelta};
+use cosmic::iced::wayland::{InitialSurface, SurfaceIdWrapper};
+use cosmic::iced::widget::{self, mouse_listener};
 use cosmic::iced_native::alignment::Horizontal;
 use cosmic::iced_native::layout::Limits;
 use cosmic::theme::Svg;
 
-use cosmic::applet::{CosmicAppletHelper, APPLET_BUTTON_THEME};
+use cosmic::applet::{cosmic_panel_config::PanelAnchor, CosmicAppletHelper, APPLET_BUTTON_THEME};
 use cosmic::widget::{button, divider, icon};
 use cosmic::Renderer;
 
@@ -13,6 +14,7 @@ use cosmic::iced::{
     widget::{column, row, slider, text, toggler},
     window, Alignment, Application, Command, Length, Subscription,
 };
+use cosmic::iced_native::{subscription, Event};
 use cosmic::iced_style::application::{self, Appearance};
 use cosmic::{Element, Theme};
 
@@ -20,15 +22,42 @@ use iced::wayland::popup::{destroy_popup, get_popup};
 use iced::widget::container;
 use iced::Color;
 
+mod config;
+mod mpris;
+#[cfg(feature = "pipewire")]
+mod pipewire_server;
 mod pulse;
-use crate::pulse::DeviceInfo;
-use libpulse_binding::volume::VolumeLinear;
+mod server;
+use crate::config::AudioConfig;
+use crate::mpris::{MprisRequest, MprisUpdate, PlaybackStatus, PlayerStatus};
+use crate::pulse::{CardInfo, DeviceInfo, StreamInfo};
+use cosmic_applet_osd::{self as osd, Osd};
+use libpulse_binding::volume::{Volume, VolumeLinear};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+// scroll distance of a wheel notch, for devices reporting pixels
+const PIXELS_PER_STEP: f32 = 15.0;
+// how long the server may take to report back the applet's own volume changes
+const OWN_CHANGE_WINDOW: Duration = Duration::from_millis(500);
 
 pub fn main() -> cosmic::iced::Result {
     pretty_env_logger::init();
 
     let helper = CosmicAppletHelper::default();
-    Audio::run(helper.window_settings())
+    let mut settings = helper.window_settings();
+    // the panel button grows when media controls are shown next to the icon
+    if let (PanelAnchor::Top | PanelAnchor::Bottom, InitialSurface::XdgWindow(w)) =
+        (&helper.anchor, &mut settings.initial_surface)
+    {
+        w.autosize = true;
+        w.size_limits = Limits::NONE
+            .min_height(1)
+            .max_height(200)
+            .min_width(1)
+            .max_width(1000);
+    }
+    Audio::run(settings)
 }
 
 #[derive(Default)]
@@ -38,13 +67,25 @@ struct Audio {
     current_input: Option<DeviceInfo>,
     outputs: Vec<DeviceInfo>,
     inputs: Vec<DeviceInfo>,
+    streams: Vec<StreamInfo>,
+    cards: Vec<CardInfo>,
     pulse_state: PulseState,
     applet_helper: CosmicAppletHelper,
     icon_name: String,
     theme: Theme,
     popup: Option<window::Id>,
     show_media_controls_in_top_panel: bool,
+    player: Option<PlayerStatus>,
+    config: AudioConfig,
+    mpris_tx: Option<UnboundedSender<MprisRequest>>,
+    osd: Osd,
     id_ctr: u32,
+    // the volume is scrolled over the panel icon
+    icon_hovered: bool,
+    // pixels scrolled that didn't make up a step yet
+    scrolled_pixels: f32,
+    // when the applet last changed the output volume or mute
+    output_set_at: Option<Instant>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -52,20 +93,131 @@ enum IsOpen {
     None,
     Output,
     Input,
+    Stream(u32),
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     SetOutputVolume(f64),
     SetInputVolume(f64),
+    SetOutputBalance(f64),
+    ToggleOutputMute,
+    ToggleInputMute,
     OutputToggle,
     InputToggle,
     OutputChanged(String),
     InputChanged(String),
+    SetStreamVolume(u32, f64),
+    ToggleStreamMute(u32),
+    StreamToggle(u32),
+    StreamOutputChanged(u32, String),
+    OutputPortChanged(String),
+    InputPortChanged(String),
+    ProfileChanged(u32, String),
     Pulse(pulse::Event),
     Ignore,
     TogglePopup,
     ToggleMediaControlsInTopPanel(bool),
+    Mpris(MprisUpdate),
+    MediaControl(MprisRequest),
+    WheelScrolled(ScrollDelta),
+    IconHovered(bool),
+    ToggleOverdrive(bool),
+    HideOsd(u32),
+}
+
+impl Audio {
+    fn output_icon_name(&self) -> &'static str {
+        let volume = self
+            .current_output
+            .as_ref()
+            .map(|o| (o.mute, VolumeLinear::from(o.volume.max()).0))
+            .unwrap_or_default();
+        match volume {
+            (true, _) => "audio-volume-muted-symbolic",
+            (false, v) if v <= 0.0 => "audio-volume-muted-symbolic",
+            (false, v) if v < 0.33 => "audio-volume-low-symbolic",
+            (false, v) if v < 0.66 => "audio-volume-medium-symbolic",
+            _ => "audio-volume-high-symbolic",
+        }
+    }
+
+    fn input_icon_name(&self) -> &'static str {
+        if self.current_input.as_ref().map_or(false, |i| i.mute) {
+            "microphone-sensitivity-muted-symbolic"
+        } else {
+            "audio-input-microphone-symbolic"
+        }
+    }
+
+    fn update_icon(&mut self) {
+        self.icon_name = self.output_icon_name().to_string();
+    }
+
+    /// Volume of the loudest channel of the default output, in percent
+    fn output_volume(&self) -> f64 {
+        volume_percent(
+            self.current_output
+                .as_ref()
+                .map(|o| o.volume.max())
+                .unwrap_or_default(),
+        )
+    }
+
+    // The popup already shows the volume when it is open
+    fn show_osd(&mut self) -> Command<Message> {
+        if self.popup.is_some() {
+            return Command::none();
+        }
+        self.osd.show(&mut self.id_ctr, Message::HideOsd)
+    }
+
+    // Scale the channels of the default output so the loudest one is at `percent`,
+    // which keeps the balance between channels
+    fn set_output_volume(&mut self, percent: f64) {
+        let percent = percent.clamp(0.0, self.config.max_volume());
+        if let Some(device) = self.current_output.as_mut() {
+            self.output_set_at = Some(Instant::now());
+            device.volume.scale(percent_volume(percent));
+            if let (Some(conn), Some(name)) = (self.pulse_state.connection(), device.name.as_ref())
+            {
+                conn.send(pulse::Message::SetSinkVolumeByName(
+                    name.clone(),
+                    device.volume,
+                ));
+            }
+        }
+        self.update_icon();
+    }
+
+    fn card(&self, device: Option<&DeviceInfo>) -> Option<&CardInfo> {
+        let index = device?.card?;
+        self.cards.iter().find(|card| card.index == index)
+    }
+
+    fn set_current_output(&mut self, mut sink: DeviceInfo) -> Command<Message> {
+        // the server reports each step of a drag or scroll back, which is
+        // behind what the applet has set since
+        if let Some(output) = self.current_output.as_ref().filter(|o| o.name == sink.name) {
+            if self
+                .output_set_at
+                .map_or(false, |at| at.elapsed() < OWN_CHANGE_WINDOW)
+            {
+                sink.volume = output.volume;
+                sink.mute = output.mute;
+            }
+        }
+        // changes made by other clients, such as volume keys
+        let changed = self.current_output.as_ref().map_or(false, |o| {
+            o.name == sink.name && (o.volume != sink.volume || o.mute != sink.mute)
+        });
+        self.current_output = Some(sink);
+        self.update_icon();
+        if changed {
+            return self.show_osd();
+        }
+        Command::none()
+    }
 }
 
 impl Application for Audio {
@@ -83,6 +235,7 @@ impl Application for Audio {
                 outputs: vec![],
                 inputs: vec![],
                 icon_name: "audio-volume-high-symbolic".to_string(),
+                config: AudioConfig::load().unwrap_or_default(),
                 ..Default::default()
             },
             Command::none(),
@@ -134,37 +287,46 @@ impl Application for Audio {
                         .max_width(400)
                         .max_height(1080);
 
-                    if let Some(conn) = self.pulse_state.connection() {
-                        conn.send(pulse::Message::GetDefaultSink);
-                        conn.send(pulse::Message::GetDefaultSource);
-                        conn.send(pulse::Message::GetSinks);
-                        conn.send(pulse::Message::GetSources);
-                    }
-
                     return get_popup(popup_settings);
                 }
             }
-            Message::SetOutputVolume(vol) => {
-                self.current_output.as_mut().map(|o| {
-                    o.volume
-                        .set(o.volume.len(), VolumeLinear(vol / 100.0).into())
-                });
-                if let PulseState::Connected(connection) = &mut self.pulse_state {
-                    if let Some(device) = &self.current_output {
-                        if let Some(name) = &device.name {
-                            connection.send(pulse::Message::SetSinkVolumeByName(
-                                name.clone(),
-                                device.volume,
-                            ))
-                        }
+            Message::SetOutputVolume(vol) => self.set_output_volume(vol),
+            Message::WheelScrolled(delta) => {
+                let steps = match delta {
+                    ScrollDelta::Lines { y, .. } => y,
+                    ScrollDelta::Pixels { y, .. } => {
+                        self.scrolled_pixels += y;
+                        let steps = (self.scrolled_pixels / PIXELS_PER_STEP).trunc();
+                        self.scrolled_pixels -= steps * PIXELS_PER_STEP;
+                        steps
                     }
+                };
+                // the popup has its own widgets to scroll
+                if self.popup.is_none() && steps != 0.0 {
+                    self.set_output_volume(
+                        self.output_volume() + steps as f64 * self.config.volume_step,
+                    );
+                    return self.show_osd();
+                }
+            }
+            Message::IconHovered(hovered) => {
+                self.icon_hovered = hovered;
+                self.scrolled_pixels = 0.0;
+            }
+            Message::HideOsd(generation) => return self.osd.hide(generation),
+            Message::ToggleOverdrive(enabled) => {
+                self.config.allow_overdrive = enabled;
+                if let Err(err) = self.config.save() {
+                    log::error!("Failed to save config: {}", err);
+                }
+                if !enabled && self.output_volume() > 100.0 {
+                    self.set_output_volume(100.0);
                 }
             }
             Message::SetInputVolume(vol) => {
-                self.current_input.as_mut().map(|i| {
-                    i.volume
-                        .set(i.volume.len(), VolumeLinear(vol / 100.0).into())
-                });
+                if let Some(device) = self.current_input.as_mut() {
+                    device.volume.scale(VolumeLinear(vol / 100.0).into());
+                }
                 if let PulseState::Connected(connection) = &mut self.pulse_state {
                     if let Some(device) = &self.current_input {
                         if let Some(name) = &device.name {
@@ -177,6 +339,48 @@ impl Application for Audio {
                     }
                 }
             }
+            Message::SetOutputBalance(balance) => {
+                if let Some(device) = self.current_output.as_mut() {
+                    self.output_set_at = Some(Instant::now());
+                    device
+                        .volume
+                        .set_balance(&device.channel_map, balance as f32);
+                    if let (Some(conn), Some(name)) =
+                        (self.pulse_state.connection(), device.name.as_ref())
+                    {
+                        conn.send(pulse::Message::SetSinkVolumeByName(
+                            name.clone(),
+                            device.volume,
+                        ));
+                    }
+                }
+            }
+            Message::ToggleOutputMute => {
+                if let Some(device) = self.current_output.as_mut() {
+                    self.output_set_at = Some(Instant::now());
+                    device.mute = !device.mute;
+                    if let (Some(conn), Some(name)) =
+                        (self.pulse_state.connection(), device.name.as_ref())
+                    {
+                        conn.send(pulse::Message::SetSinkMuteByName(name.clone(), device.mute));
+                    }
+                }
+                self.update_icon();
+                return self.show_osd();
+            }
+            Message::ToggleInputMute => {
+                if let Some(device) = self.current_input.as_mut() {
+                    device.mute = !device.mute;
+                    if let (Some(conn), Some(name)) =
+                        (self.pulse_state.connection(), device.name.as_ref())
+                    {
+                        conn.send(pulse::Message::SetSourceMuteByName(
+                            name.clone(),
+                            device.mute,
+                        ));
+                    }
+                }
+            }
             Message::OutputChanged(val) => {
                 if let Some(conn) = self.pulse_state.connection() {
                     if let Some(val) = self.outputs.iter().find(|o| o.name.as_ref() == Some(&val)) {
@@ -191,6 +395,60 @@ impl Application for Audio {
                     }
                 }
             }
+            Message::SetStreamVolume(index, vol) => {
+                let vol = vol.clamp(0.0, self.config.max_volume());
+                if let Some(stream) = self.streams.iter_mut().find(|s| s.index == index) {
+                    stream.volume.scale(percent_volume(vol));
+                    if let Some(conn) = self.pulse_state.connection() {
+                        conn.send(pulse::Message::SetSinkInputVolume(index, stream.volume));
+                    }
+                }
+            }
+            Message::ToggleStreamMute(index) => {
+                if let Some(stream) = self.streams.iter_mut().find(|s| s.index == index) {
+                    stream.mute = !stream.mute;
+                    if let Some(conn) = self.pulse_state.connection() {
+                        conn.send(pulse::Message::SetSinkInputMute(index, stream.mute));
+                    }
+                }
+            }
+            Message::StreamToggle(index) => {
+                self.is_open = if self.is_open == IsOpen::Stream(index) {
+                    IsOpen::None
+                } else {
+                    if let Some(conn) = self.pulse_state.connection() {
+                        conn.send(pulse::Message::GetSinks);
+                    }
+                    IsOpen::Stream(index)
+                }
+            }
+            Message::StreamOutputChanged(index, sink) => {
+                self.is_open = IsOpen::None;
+                if let Some(conn) = self.pulse_state.connection() {
+                    conn.send(pulse::Message::MoveSinkInput(index, sink));
+                }
+            }
+            Message::OutputPortChanged(port) => {
+                if let (Some(conn), Some(name)) = (
+                    self.pulse_state.connection(),
+                    self.current_output.as_ref().and_then(|o| o.name.clone()),
+                ) {
+                    conn.send(pulse::Message::SetSinkPortByName(name, port));
+                }
+            }
+            Message::InputPortChanged(port) => {
+                if let (Some(conn), Some(name)) = (
+                    self.pulse_state.connection(),
+                    self.current_input.as_ref().and_then(|i| i.name.clone()),
+                ) {
+                    conn.send(pulse::Message::SetSourcePortByName(name, port));
+                }
+            }
+            Message::ProfileChanged(card, profile) => {
+                if let Some(conn) = self.pulse_state.connection() {
+                    conn.send(pulse::Message::SetCardProfile(card, profile));
+                }
+            }
             Message::OutputToggle => {
                 self.is_open = if self.is_open == IsOpen::Output {
                     IsOpen::None
@@ -221,6 +479,8 @@ impl Application for Audio {
                         conn.send(pulse::Message::GetSources);
                         conn.send(pulse::Message::GetDefaultSink);
                         conn.send(pulse::Message::GetDefaultSource);
+                        conn.send(pulse::Message::GetSinkInputs);
+                        conn.send(pulse::Message::GetCards);
                     }
                 }
                 pulse::Event::MessageReceived(msg) => {
@@ -230,21 +490,60 @@ impl Application for Audio {
                         pulse::Message::SetSources(sources) => {
                             self.inputs = sources
                                 .into_iter()
-                                .filter(|source| {
-                                    !source
-                                        .name
-                                        .as_ref()
-                                        .unwrap_or(&String::from("Generic"))
-                                        .contains("monitor")
-                                })
+                                .filter(|source| !is_monitor(source))
                                 .collect()
                         }
                         pulse::Message::SetDefaultSink(sink) => {
-                            self.current_output = Some(sink);
+                            return self.set_current_output(sink);
                         }
                         pulse::Message::SetDefaultSource(source) => {
                             self.current_input = Some(source)
                         }
+                        pulse::Message::SetSinkInputs(streams) => {
+                            if let IsOpen::Stream(index) = self.is_open {
+                                if !streams.iter().any(|s| s.index == index) {
+                                    self.is_open = IsOpen::None;
+                                }
+                            }
+                            self.streams = streams;
+                        }
+                        pulse::Message::SetCards(cards) => self.cards = cards,
+                        pulse::Message::SinkChanged(sink) => {
+                            upsert(&mut self.outputs, sink.clone(), |o| o.index);
+                            if self.current_output.as_ref().map(|o| o.index) == Some(sink.index) {
+                                return self.set_current_output(sink);
+                            }
+                        }
+                        pulse::Message::SinkRemoved(index) => {
+                            self.outputs.retain(|o| o.index != index)
+                        }
+                        pulse::Message::SourceChanged(source) => {
+                            if is_monitor(&source) {
+                                return Command::none();
+                            }
+                            if self.current_input.as_ref().map(|i| i.index) == Some(source.index) {
+                                self.current_input = Some(source.clone());
+                            }
+                            upsert(&mut self.inputs, source, |i| i.index);
+                        }
+                        pulse::Message::SourceRemoved(index) => {
+                            self.inputs.retain(|i| i.index != index)
+                        }
+                        pulse::Message::SinkInputChanged(stream) => {
+                            upsert(&mut self.streams, stream, |s| s.index)
+                        }
+                        pulse::Message::SinkInputRemoved(index) => {
+                            if self.is_open == IsOpen::Stream(index) {
+                                self.is_open = IsOpen::None;
+                            }
+                            self.streams.retain(|s| s.index != index);
+                        }
+                        pulse::Message::CardChanged(card) => {
+                            upsert(&mut self.cards, card, |c| c.index)
+                        }
+                        pulse::Message::CardRemoved(index) => {
+                            self.cards.retain(|c| c.index != index)
+                        }
                         pulse::Message::Disconnected => {
                             panic!("Subscriton error handling is bad. This should never happen.")
                         }
@@ -259,54 +558,108 @@ impl Application for Audio {
             Message::ToggleMediaControlsInTopPanel(enabled) => {
                 self.show_media_controls_in_top_panel = enabled;
             }
+            Message::Mpris(update) => match update {
+                MprisUpdate::Init(player, tx) => {
+                    self.player = player;
+                    self.mpris_tx = Some(tx);
+                }
+                MprisUpdate::Player(player) => self.player = player,
+                MprisUpdate::Error(err) => {
+                    log::error!("MPRIS error: {}", err);
+                }
+            },
+            Message::MediaControl(request) => {
+                if let Some(tx) = self.mpris_tx.as_ref() {
+                    let _ = tx.send(request);
+                }
+            }
         };
 
         Command::none()
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        pulse::connect().map(Message::Pulse)
+        let mut subscriptions = vec![
+            pulse::connect().map(Message::Pulse),
+            mpris::mpris_subscription(0).map(|(_, update)| Message::Mpris(update)),
+        ];
+        // only scrolling over the panel icon changes the volume
+        if self.icon_hovered {
+            subscriptions.push(subscription::events_with(|e, _| match e {
+                Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                    Some(Message::WheelScrolled(delta))
+                }
+                _ => None,
+            }));
+        }
+        Subscription::batch(subscriptions)
     }
 
     fn view(&self, id: SurfaceIdWrapper) -> Element<Message> {
         match id {
-            SurfaceIdWrapper::LayerSurface(_) => unimplemented!(),
-            SurfaceIdWrapper::Window(_) => self
+            SurfaceIdWrapper::LayerSurface(_) => self
                 .applet_helper
-                .icon_button(&self.icon_name)
-                .on_press(Message::TogglePopup)
+                .popup_container(osd::view(
+                    self.output_icon_name(),
+                    self.output_volume(),
+                    self.config.max_volume(),
+                ))
                 .into(),
+            SurfaceIdWrapper::Window(_) => {
+                let icon_button = mouse_listener(
+                    self.applet_helper
+                        .icon_button(&self.icon_name)
+                        .on_press(Message::TogglePopup),
+                )
+                .on_middle_release(Message::ToggleOutputMute)
+                .on_mouse_enter(Message::IconHovered(true))
+                .on_mouse_exit(Message::IconHovered(false));
+                match (&self.player, &self.applet_helper.anchor) {
+                    (Some(player), PanelAnchor::Top | PanelAnchor::Bottom)
+                        if self.show_media_controls_in_top_panel =>
+                    {
+                        // smaller than the applet icon, so the controls fit in the panel
+                        let size = self.applet_helper.suggested_size().0 * 3 / 4;
+                        row![icon_button, media_controls(player, size, 4)]
+                            .align_items(Alignment::Center)
+                            .into()
+                    }
+                    _ => icon_button.into(),
+                }
+            }
             SurfaceIdWrapper::Popup(_) => {
                 let audio_disabled = matches!(self.pulse_state, PulseState::Disconnected(_));
-                let out_f64 = VolumeLinear::from(
-                    self.current_output
-                        .as_ref()
-                        .map(|o| o.volume.avg())
-                        .unwrap_or_default(),
-                )
-                .0 * 100.0;
+                let out_f64 = self.output_volume();
                 let in_f64 = VolumeLinear::from(
                     self.current_input
                         .as_ref()
-                        .map(|o| o.volume.avg())
+                        .map(|o| o.volume.max())
                         .unwrap_or_default(),
                 )
                 .0 * 100.0;
 
-                let audio_content = if audio_disabled {
-                    column![text("PulseAudio Disconnected")
+                let mut audio_content = if audio_disabled {
+                    column![text("Sound Server Disconnected")
                         .width(Length::Fill)
                         .horizontal_alignment(Horizontal::Center)
                         .size(24),]
                 } else {
                     column![
                         row![
-                            icon("audio-volume-high-symbolic", 32)
-                                .width(Length::Units(24))
-                                .height(Length::Units(24))
-                                .style(Svg::Symbolic),
-                            slider(0.0..=100.0, out_f64, Message::SetOutputVolume)
-                                .width(Length::FillPortion(5)),
+                            button(APPLET_BUTTON_THEME)
+                                .custom(vec![icon(self.output_icon_name(), 32)
+                                    .width(Length::Units(24))
+                                    .height(Length::Units(24))
+                                    .style(Svg::Symbolic)
+                                    .into()])
+                                .padding(0)
+                                .on_press(Message::ToggleOutputMute),
+                            slider(
+                                0.0..=self.config.max_volume(),
+                                out_f64,
+                                Message::SetOutputVolume
+                            )
+                            .width(Length::FillPortion(5)),
                             text(format!("{}%", out_f64.round()))
                                 .width(Length::FillPortion(1))
                                 .horizontal_alignment(Horizontal::Right)
@@ -314,11 +667,16 @@ impl Application for Audio {
                         .spacing(12)
                         .align_items(Alignment::Center)
                         .padding([8, 24]),
+                        balance_row(self.current_output.as_ref()),
                         row![
-                            icon("audio-input-microphone-symbolic", 32)
-                                .width(Length::Units(24))
-                                .height(Length::Units(24))
-                                .style(Svg::Symbolic),
+                            button(APPLET_BUTTON_THEME)
+                                .custom(vec![icon(self.input_icon_name(), 32)
+                                    .width(Length::Units(24))
+                                    .height(Length::Units(24))
+                                    .style(Svg::Symbolic)
+                                    .into()])
+                                .padding(0)
+                                .on_press(Message::ToggleInputMute),
                             slider(0.0..=100.0, in_f64, Message::SetInputVolume)
                                 .width(Length::FillPortion(5)),
                             text(format!("{}%", in_f64.round()))
@@ -348,7 +706,13 @@ impl Application for Audio {
                                 .collect(),
                             Message::OutputToggle,
                             Message::OutputChanged,
-                        ),
+                        )
+                        .push(device_options(
+                            self.is_open == IsOpen::Output,
+                            self.current_output.as_ref(),
+                            self.card(self.current_output.as_ref()),
+                            Message::OutputPortChanged,
+                        )),
                         revealer(
                             self.is_open == IsOpen::Input,
                             "Input",
@@ -367,9 +731,58 @@ impl Application for Audio {
                             Message::InputToggle,
                             Message::InputChanged,
                         )
+                        .push(device_options(
+                            self.is_open == IsOpen::Input,
+                            self.current_input.as_ref(),
+                            self.card(self.current_input.as_ref()),
+                            Message::InputPortChanged,
+                        ))
                     ]
                     .align_items(Alignment::Start)
                 };
+                if !audio_disabled && !self.streams.is_empty() {
+                    audio_content = audio_content.push(
+                        container(divider::horizontal::light())
+                            .padding([12, 24])
+                            .width(Length::Fill),
+                    );
+                    for stream in &self.streams {
+                        audio_content = audio_content
+                            .push(revealer(
+                                self.is_open == IsOpen::Stream(stream.index),
+                                &pretty_name(stream.name.clone()),
+                                match self.outputs.iter().find(|o| o.index == stream.sink) {
+                                    Some(output) => pretty_name(output.description.clone()),
+                                    None => String::from("No device selected"),
+                                },
+                                self.outputs
+                                    .clone()
+                                    .into_iter()
+                                    .map(|output| {
+                                        (
+                                            output.name.clone().unwrap_or_default(),
+                                            pretty_name(output.description),
+                                        )
+                                    })
+                                    .collect(),
+                                Message::StreamToggle(stream.index),
+                                {
+                                    let index = stream.index;
+                                    move |sink| Message::StreamOutputChanged(index, sink)
+                                },
+                            ))
+                            .push(stream_row(stream, self.config.max_volume()));
+                    }
+                }
+                if let Some(player) = self.player.as_ref() {
+                    audio_content = audio_content
+                        .push(
+                            container(divider::horizontal::light())
+                                .padding([12, 24])
+                                .width(Length::Fill),
+                        )
+                        .push(now_playing(player));
+                }
                 let content = column![
                     audio_content,
                     container(divider::horizontal::light())
@@ -381,6 +794,12 @@ impl Application for Audio {
                         Message::ToggleMediaControlsInTopPanel,
                     ))
                     .padding([0, 24]),
+                    container(toggler(
+                        Some("Allow Volume Above 100%".into()),
+                        self.config.allow_overdrive,
+                        Message::ToggleOverdrive,
+                    ))
+                    .padding([8, 24, 0, 24]),
                     container(divider::horizontal::light())
                         .padding([12, 24])
                         .width(Length::Fill),
@@ -400,6 +819,163 @@ impl Application for Audio {
     }
 }
 
+// Left/right balance of the output, only for channel maps with sides
+fn balance_row(device: Option<&DeviceInfo>) -> Element<'static, Message> {
+    let device = match device.filter(|d| d.channel_map.can_balance()) {
+        Some(device) => device,
+        None => return column![].into(),
+    };
+    let balance = device.volume.get_balance(&device.channel_map) as f64;
+    row![
+        text("L").width(Length::Units(24)),
+        slider(-1.0..=1.0, balance, Message::SetOutputBalance)
+            .step(0.01)
+            .width(Length::FillPortion(5)),
+        text("R")
+            .width(Length::FillPortion(1))
+            .horizontal_alignment(Horizontal::Right)
+    ]
+    .spacing(12)
+    .align_items(Alignment::Center)
+    .padding([0, 24, 8, 24])
+    .into()
+}
+
+// Up to 100% the sliders are linear, above it they follow PulseAudio's volume
+// scale so the overdrive percentages are the ones other mixers show
+fn volume_percent(volume: Volume) -> f64 {
+    if volume <= Volume::NORMAL {
+        VolumeLinear::from(volume).0 * 100.0
+    } else {
+        volume.0 as f64 / Volume::NORMAL.0 as f64 * 100.0
+    }
+}
+
+fn percent_volume(percent: f64) -> Volume {
+    if percent <= 100.0 {
+        VolumeLinear(percent / 100.0).into()
+    } else {
+        Volume((Volume::NORMAL.0 as f64 * percent / 100.0) as u32)
+    }
+}
+
+// `max_volume` in percent, the same range as the output slider
+fn stream_row(stream: &StreamInfo, max_volume: f64) -> Element<Message> {
+    let index = stream.index;
+    let volume = volume_percent(stream.volume.max());
+    let stream_icon: Element<_> = if stream.mute {
+        icon("audio-volume-muted-symbolic", 32)
+            .width(Length::Units(24))
+            .height(Length::Units(24))
+            .style(Svg::Symbolic)
+            .into()
+    } else {
+        icon(
+            stream
+                .icon_name
+                .as_deref()
+                .unwrap_or("application-x-executable"),
+            32,
+        )
+        .width(Length::Units(24))
+        .height(Length::Units(24))
+        .into()
+    };
+    row![
+        button(APPLET_BUTTON_THEME)
+            .custom(vec![stream_icon])
+            .padding(0)
+            .on_press(Message::ToggleStreamMute(index)),
+        slider(0.0..=max_volume, volume, move |v| Message::SetStreamVolume(
+            index, v
+        ))
+        .width(Length::FillPortion(5)),
+        text(format!("{}%", volume.round()))
+            .width(Length::FillPortion(1))
+            .horizontal_alignment(Horizontal::Right)
+    ]
+    .spacing(12)
+    .align_items(Alignment::Center)
+    .padding([8, 24])
+    .into()
+}
+
+// Cover, track info and controls of the active player
+fn now_playing(player: &PlayerStatus) -> Element<Message> {
+    let art = match player.art.as_deref() {
+        Some(path) => icon(path, 64)
+            .width(Length::Units(64))
+            .height(Length::Units(64)),
+        None => icon("audio-x-generic-symbolic", 64)
+            .width(Length::Units(64))
+            .height(Length::Units(64))
+            .style(Svg::Symbolic),
+    };
+    let mut info = column![text(pretty_name(
+        player.title.clone().or_else(|| player.identity.clone())
+    ))
+    .size(16)]
+    .spacing(4)
+    .width(Length::Fill);
+    if let Some(artists) = player.artists.as_ref() {
+        info = info.push(text(artists).size(14));
+    }
+    if let Some(album) = player.album.as_ref() {
+        info = info.push(text(album).size(12));
+    }
+    column![
+        row![art, info].spacing(12).align_items(Alignment::Center),
+        container(media_controls(player, 24, 8))
+            .width(Length::Fill)
+            .center_x()
+    ]
+    .spacing(8)
+    .padding([8, 24])
+    .into()
+}
+
+fn media_controls(
+    player: &PlayerStatus,
+    icon_size: u16,
+    padding: u16,
+) -> Element<'static, Message> {
+    let control = |name: &'static str, enabled: bool, request: MprisRequest| {
+        let button = button(APPLET_BUTTON_THEME)
+            .custom(vec![icon(name, icon_size)
+                .width(Length::Units(icon_size))
+                .height(Length::Units(icon_size))
+                .style(Svg::Symbolic)
+                .into()])
+            .padding(padding);
+        if enabled {
+            button.on_press(Message::MediaControl(request))
+        } else {
+            button
+        }
+    };
+    let (play_pause_icon, can_play_pause) = if player.status == PlaybackStatus::Playing {
+        ("media-playback-pause-symbolic", player.can_pause)
+    } else {
+        ("media-playback-start-symbolic", player.can_play)
+    };
+    row![
+        control(
+            "media-skip-backward-symbolic",
+            player.can_go_previous,
+            MprisRequest::Previous
+        ),
+        control(play_pause_icon, can_play_pause, MprisRequest::PlayPause),
+        control(
+            "media-skip-forward-symbolic",
+            player.can_go_next,
+            MprisRequest::Next
+        ),
+    ]
+    .spacing(4)
+    .align_items(Alignment::Center)
+    .into()
+}
+
 fn revealer(
     open: bool,
     title: &str,
@@ -426,6 +1002,75 @@ fn revealer(
     }
 }
 
+// Ports of the selected device and profiles of its card, unavailable ones can't be selected
+fn device_options(
+    open: bool,
+    device: Option<&DeviceInfo>,
+    card: Option<&CardInfo>,
+    port_changed: impl Fn(String) -> Message,
+) -> widget::Column<'static, Message, Renderer> {
+    let mut options = column![].width(Length::Fill);
+    if !open {
+        return options;
+    }
+    if let Some(device) = device.filter(|d| d.ports.len() > 1) {
+        options = options.push(
+            container(text("Port").size(14))
+                .padding([8, 24, 0, 24])
+                .width(Length::Fill),
+        );
+        for port in &device.ports {
+            options = options.push(option_button(
+                pretty_name(port.description.clone()),
+                device.active_port.as_ref() == Some(&port.name),
+                port.available.then(|| port_changed(port.name.clone())),
+            ));
+        }
+    }
+    if let Some(card) = card.filter(|c| c.profiles.len() > 1) {
+        options = options.push(
+            container(text("Profile").size(14))
+                .padding([8, 24, 0, 24])
+                .width(Length::Fill),
+        );
+        for profile in &card.profiles {
+            options = options.push(option_button(
+                pretty_name(profile.description.clone()),
+                card.active_profile.as_ref() == Some(&profile.name),
+                profile
+                    .available
+                    .then(|| Message::ProfileChanged(card.index, profile.name.clone())),
+            ));
+        }
+    }
+    options
+}
+
+// a button without message is drawn disabled
+fn option_button(
+    name: String,
+    selected: bool,
+    on_press: Option<Message>,
+) -> widget::Button<'static, Message, Renderer> {
+    let mut content = vec![text(name).width(Length::Fill).into()];
+    if selected {
+        content.push(
+            icon("emblem-ok-symbolic", 12)
+                .size(12)
+                .style(Svg::Symbolic)
+                .into(),
+        );
+    }
+    let button = button(APPLET_BUTTON_THEME)
+        .custom(content)
+        .width(Length::Fill)
+        .padding([8, 48]);
+    match on_press {
+        Some(message) => button.on_press(message),
+        None => button,
+    }
+}
+
 fn revealer_head(
     _open: bool,
     title: &str,
@@ -442,6 +1087,21 @@ fn revealer_head(
         .on_press(toggle)
 }
 
+fn is_monitor(source: &DeviceInfo) -> bool {
+    source
+        .name
+        .as_ref()
+        .map_or(false, |name| name.contains("monitor"))
+}
+
+// Replace the item with the same index, or add it in index order
+fn upsert<T>(items: &mut Vec<T>, item: T, index: impl Fn(&T) -> u32) {
+    match items.binary_search_by_key(&index(&item), &index) {
+        Ok(i) => items[i] = item,
+        Err(i) => items.insert(i, item),
+    }
+}
+
 fn pretty_name(name: Option<String>) -> String {
     match name {
         Some(n) => n,
@@ -0,0 +1,407 @@
+//! # MPRIS client
+//!
+//! Watches the session bus for `org.mpris.MediaPlayer2.*` names and reports the
+//! status of the active player, which is one that is playing or else the last
+//! one which appeared.
+
+use cosmic::iced;
+use cosmic::iced_native::subscription;
+use futures::{
+    stream::{abortable, AbortHandle, Abortable, BoxStream, SelectAll},
+    StreamExt,
+};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::path::PathBuf;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use url::Url;
+use zbus::{
+    dbus_proxy, fdo,
+    zvariant::{OwnedValue, Value},
+    CacheProperties, Connection,
+};
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+
+#[dbus_proxy(
+    interface = "org.mpris.MediaPlayer2",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait MediaPlayer2 {
+    /// Identity property
+    #[dbus_proxy(property)]
+    fn identity(&self) -> zbus::Result<String>;
+}
+
+#[dbus_proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Player {
+    /// Next method
+    fn next(&self) -> zbus::Result<()>;
+
+    /// PlayPause method
+    fn play_pause(&self) -> zbus::Result<()>;
+
+    /// Previous method
+    fn previous(&self) -> zbus::Result<()>;
+
+    /// CanGoNext property
+    #[dbus_proxy(property)]
+    fn can_go_next(&self) -> zbus::Result<bool>;
+
+    /// CanGoPrevious property
+    #[dbus_proxy(property)]
+    fn can_go_previous(&self) -> zbus::Result<bool>;
+
+    /// CanPause property
+    #[dbus_proxy(property)]
+    fn can_pause(&self) -> zbus::Result<bool>;
+
+    /// CanPlay property
+    #[dbus_proxy(property)]
+    fn can_play(&self) -> zbus::Result<bool>;
+
+    /// Metadata property
+    #[dbus_proxy(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+    /// PlaybackStatus property
+    #[dbus_proxy(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+impl From<&str> for PlaybackStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "Playing" => PlaybackStatus::Playing,
+            "Paused" => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PlayerStatus {
+    pub identity: Option<String>,
+    pub title: Option<String>,
+    pub artists: Option<String>,
+    pub album: Option<String>,
+    /// Local cover art. Remote `http(s)://` art is not downloaded, most players
+    /// cache it to a file and report that instead.
+    pub art: Option<PathBuf>,
+    pub status: PlaybackStatus,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    pub can_pause: bool,
+    pub can_play: bool,
+}
+
+impl PlayerStatus {
+    async fn new(
+        player: &PlayerProxy<'_>,
+        media_player: &MediaPlayer2Proxy<'_>,
+    ) -> zbus::Result<Self> {
+        let metadata = player.metadata().await.unwrap_or_default();
+        Ok(PlayerStatus {
+            identity: media_player.identity().await.ok(),
+            title: metadata.get("xesam:title").and_then(string),
+            artists: metadata.get("xesam:artist").and_then(strings),
+            album: metadata.get("xesam:album").and_then(string),
+            art: metadata
+                .get("mpris:artUrl")
+                .and_then(string)
+                .and_then(|url| art_path(&url)),
+            status: PlaybackStatus::from(player.playback_status().await?.as_str()),
+            can_go_next: player.can_go_next().await.unwrap_or_default(),
+            can_go_previous: player.can_go_previous().await.unwrap_or_default(),
+            can_pause: player.can_pause().await.unwrap_or_default(),
+            can_play: player.can_play().await.unwrap_or_default(),
+        })
+    }
+}
+
+fn string(value: &OwnedValue) -> Option<String> {
+    match &**value {
+        Value::Str(s) if !s.is_empty() => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+// `file://` art URLs are percent-encoded like any other URL
+fn art_path(url: &str) -> Option<PathBuf> {
+    Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")?
+        .to_file_path()
+        .ok()
+}
+
+// artists are a list of strings, they are shown joined
+fn strings(value: &OwnedValue) -> Option<String> {
+    match &**value {
+        Value::Array(array) => {
+            let strings: Vec<_> = array
+                .get()
+                .iter()
+                .filter_map(|v| match v {
+                    Value::Str(s) if !s.is_empty() => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect();
+            (!strings.is_empty()).then(|| strings.join(", "))
+        }
+        _ => string(value),
+    }
+}
+
+pub fn mpris_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
+    id: I,
+) -> iced::Subscription<(I, MprisUpdate)> {
+    subscription::unfold(id, State::Ready, move |state| start_listening(id, state))
+}
+
+pub enum State {
+    Ready,
+    Waiting(Box<Watcher>),
+    Finished,
+}
+
+pub struct Watcher {
+    conn: Connection,
+    rx: UnboundedReceiver<MprisRequest>,
+    name_changes: fdo::NameOwnerChangedStream<'static>,
+    // yields the bus name of the player whose properties changed
+    property_changes: SelectAll<Abortable<BoxStream<'static, String>>>,
+    // in the order they appeared
+    players: Vec<MprisPlayer>,
+    active: Option<String>,
+}
+
+// A player on the bus and its last known status
+struct MprisPlayer {
+    name: String,
+    player: PlayerProxy<'static>,
+    media_player: MediaPlayer2Proxy<'static>,
+    status: Option<PlayerStatus>,
+    // ends the property changes stream of the player once it is gone
+    changes: AbortHandle,
+}
+
+impl MprisPlayer {
+    async fn new(
+        conn: &Connection,
+        name: String,
+    ) -> zbus::Result<(Self, Abortable<BoxStream<'static, String>>)> {
+        // properties are read when something changed, so they can't come from a stale cache
+        let player = PlayerProxy::builder(conn)
+            .destination(name.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        let media_player = MediaPlayer2Proxy::builder(conn)
+            .destination(name.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        let properties = fdo::PropertiesProxy::builder(conn)
+            .destination(name.clone())?
+            .path(MPRIS_PATH)?
+            .build()
+            .await?;
+        let changed = name.clone();
+        let (changes, handle) = abortable(
+            properties
+                .receive_properties_changed()
+                .await?
+                .map(move |_| changed.clone())
+                .boxed(),
+        );
+        let status = PlayerStatus::new(&player, &media_player).await.ok();
+        Ok((
+            MprisPlayer {
+                name,
+                player,
+                media_player,
+                status,
+                changes: handle,
+            },
+            changes,
+        ))
+    }
+
+    async fn refresh(&mut self) {
+        self.status = PlayerStatus::new(&self.player, &self.media_player)
+            .await
+            .ok();
+    }
+}
+
+impl Watcher {
+    async fn new(conn: Connection, rx: UnboundedReceiver<MprisRequest>) -> zbus::Result<Self> {
+        let dbus = fdo::DBusProxy::new(&conn).await?;
+        let name_changes = dbus.receive_name_owner_changed().await?;
+        let mut watcher = Watcher {
+            conn,
+            rx,
+            name_changes,
+            property_changes: SelectAll::new(),
+            players: Vec::new(),
+            active: None,
+        };
+        for name in dbus.list_names().await? {
+            if name.as_str().starts_with(MPRIS_PREFIX) {
+                watcher.add_player(name.to_string()).await;
+            }
+        }
+        Ok(watcher)
+    }
+
+    async fn add_player(&mut self, name: String) {
+        // a name which changed owner is a new player
+        self.remove_player(&name);
+        match MprisPlayer::new(&self.conn, name.clone()).await {
+            Ok((player, changes)) => {
+                self.property_changes.push(changes);
+                self.players.push(player);
+            }
+            Err(e) => log::warn!("Failed to watch {}: {}", name, e),
+        }
+    }
+
+    fn remove_player(&mut self, name: &str) {
+        self.players.retain(|player| {
+            if player.name == name {
+                player.changes.abort();
+            }
+            player.name != name
+        });
+    }
+
+    // Pick the active player from the known statuses
+    fn status(&mut self) -> Option<PlayerStatus> {
+        let mut known = self
+            .players
+            .iter()
+            .rev()
+            .filter_map(|player| Some((&player.name, player.status.as_ref()?)));
+        let last = known.clone().next();
+        let active = known
+            .find(|(_, status)| status.status == PlaybackStatus::Playing)
+            .or(last);
+        self.active = active.map(|(name, _)| name.clone());
+        active.map(|(_, status)| status.clone())
+    }
+
+    async fn request(&self, request: MprisRequest) -> zbus::Result<()> {
+        let player = match self
+            .players
+            .iter()
+            .find(|player| Some(&player.name) == self.active.as_ref())
+        {
+            Some(player) => &player.player,
+            None => return Ok(()),
+        };
+        match request {
+            MprisRequest::PlayPause => player.play_pause().await,
+            MprisRequest::Next => player.next().await,
+            MprisRequest::Previous => player.previous().await,
+        }
+    }
+}
+
+async fn start_listening<I: Copy>(id: I, state: State) -> (Option<(I, MprisUpdate)>, State) {
+    match state {
+        State::Ready => {
+            let conn = match Connection::session().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    return (
+                        Some((id, MprisUpdate::Error(e.to_string()))),
+                        State::Finished,
+                    )
+                }
+            };
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            let mut watcher = match Watcher::new(conn, rx).await {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    return (
+                        Some((id, MprisUpdate::Error(e.to_string()))),
+                        State::Finished,
+                    )
+                }
+            };
+            let status = watcher.status();
+            (
+                Some((id, MprisUpdate::Init(status, tx))),
+                State::Waiting(Box::new(watcher)),
+            )
+        }
+        State::Waiting(mut watcher) => {
+            tokio::select! {
+                request = watcher.rx.recv() => match request {
+                    Some(request) => {
+                        if let Err(e) = watcher.request(request).await {
+                            log::warn!("MPRIS request failed: {}", e);
+                        }
+                        // the new state is pushed by the player
+                        return (None, State::Waiting(watcher));
+                    }
+                    None => return (None, State::Finished),
+                },
+                Some(change) = watcher.name_changes.next() => {
+                    let args = match change.args() {
+                        Ok(args) => args,
+                        Err(_) => return (None, State::Waiting(watcher)),
+                    };
+                    let name = args.name().to_string();
+                    if !name.starts_with(MPRIS_PREFIX) {
+                        return (None, State::Waiting(watcher));
+                    }
+                    if args.new_owner().is_some() {
+                        watcher.add_player(name).await;
+                    } else {
+                        watcher.remove_player(&name);
+                    }
+                }
+                Some(name) = watcher.property_changes.next() => {
+                    if let Some(player) = watcher.players.iter_mut().find(|p| p.name == name) {
+                        player.refresh().await;
+                    }
+                }
+            };
+            let status = watcher.status();
+            (
+                Some((id, MprisUpdate::Player(status))),
+                State::Waiting(watcher),
+            )
+        }
+        State::Finished => iced::futures::future::pending().await,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MprisRequest {
+    PlayPause,
+    Next,
+    Previous,
+}
+
+#[derive(Debug, Clone)]
+pub enum MprisUpdate {
+    Init(Option<PlayerStatus>, UnboundedSender<MprisRequest>),
+    Player(Option<PlayerStatus>),
+    Error(String),
+}
@@ -0,0 +1,1083 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::pulse::{CardInfo, DeviceInfo, PortInfo, ProfileInfo, ServerInfo, StreamInfo};
+use crate::server::{push_change, AudioServer, Change, ObjectKind, ServerError};
+use libpulse_binding::{
+    channelmap::{self, Position},
+    volume::{ChannelVolumes, VolumeLinear},
+};
+use pipewire as pw;
+use pw::{
+    context::Context,
+    core::{Core, PW_ID_CORE},
+    device::{Device, DeviceListener},
+    main_loop::MainLoop,
+    metadata::{Metadata, MetadataListener},
+    node::{Node, NodeListener},
+    registry::Registry,
+    spa::{
+        param::ParamType,
+        pod::{
+            deserialize::PodDeserializer, serialize::PodSerializer, Object, Pod, Property, Value,
+            ValueArray,
+        },
+        utils::{result::AsyncSeq, Direction, Id, SpaTypes},
+    },
+    types::ObjectType,
+};
+use serde::{Deserialize, Serialize};
+
+// how long to wait for the daemon to answer before giving up on it
+const ROUNDTRIP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Sink,
+    Source,
+    Stream,
+}
+
+impl NodeKind {
+    fn from_media_class(media_class: &str) -> Option<Self> {
+        match media_class {
+            "Audio/Sink" => Some(NodeKind::Sink),
+            "Audio/Source" | "Audio/Source/Virtual" => Some(NodeKind::Source),
+            "Stream/Output/Audio" => Some(NodeKind::Stream),
+            _ => None,
+        }
+    }
+
+    fn object_kind(self) -> ObjectKind {
+        match self {
+            NodeKind::Sink => ObjectKind::Sink,
+            NodeKind::Source => ObjectKind::Source,
+            NodeKind::Stream => ObjectKind::SinkInput,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct NodeState {
+    kind: NodeKind,
+    name: Option<String>,
+    description: Option<String>,
+    app_name: Option<String>,
+    icon_name: Option<String>,
+    // linear volume of each channel
+    volumes: Vec<f32>,
+    // SPA audio channel positions
+    channels: Vec<u32>,
+    mute: bool,
+    // device id of the card and index of the node in the card profile
+    card: Option<u32>,
+    card_device: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    direction: Direction,
+    port: PortInfo,
+    // card profile devices and profiles the route can be used with
+    devices: Vec<i32>,
+    profiles: Vec<i32>,
+}
+
+// Profiles and routes are enumerated through the params of the card device
+#[derive(Debug, Clone, Default)]
+struct CardState {
+    name: Option<String>,
+    description: Option<String>,
+    profiles: BTreeMap<i32, ProfileInfo>,
+    active_profile: Option<i32>,
+    routes: BTreeMap<i32, Route>,
+    // card profile device -> active route
+    active_routes: HashMap<i32, i32>,
+}
+
+impl CardState {
+    fn update_param(&mut self, param_type: ParamType, object: Object) {
+        match param_type {
+            ParamType::EnumProfile => {
+                if let Some((index, profile)) = parse_profile(object) {
+                    self.profiles.insert(index, profile);
+                }
+            }
+            ParamType::Profile => {
+                if let Some((index, _)) = parse_profile(object) {
+                    self.active_profile = Some(index);
+                }
+            }
+            ParamType::EnumRoute => {
+                if let Some((index, _, route)) = parse_route(object) {
+                    self.routes.insert(index, route);
+                }
+            }
+            ParamType::Route => {
+                if let Some((index, Some(device), _)) = parse_route(object) {
+                    self.active_routes.insert(device, index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Routes of the card which can be used by a node of the active profile
+    fn ports(
+        &self,
+        direction: Direction,
+        card_device: i32,
+    ) -> impl Iterator<Item = (&i32, &Route)> {
+        let active_profile = self.active_profile;
+        self.routes.iter().filter(move |(_, route)| {
+            route.direction == direction
+                && route.devices.contains(&card_device)
+                && active_profile.map_or(true, |profile| route.profiles.contains(&profile))
+        })
+    }
+}
+
+// State of the PipeWire graph, kept up to date by the proxy listeners
+#[derive(Default)]
+struct Graph {
+    nodes: HashMap<u32, NodeState>,
+    // link id -> (output node, input node)
+    links: HashMap<u32, (u32, u32)>,
+    cards: HashMap<u32, CardState>,
+    default_sink: Option<String>,
+    default_source: Option<String>,
+    changes: Vec<Change>,
+    done: Option<AsyncSeq>,
+    error: Option<String>,
+}
+
+impl Graph {
+    fn changed(&mut self, change: Change) {
+        push_change(&mut self.changes, change);
+    }
+
+    fn update_props(&mut self, id: u32, pod: &Pod) {
+        let node = match self.nodes.get_mut(&id) {
+            Some(node) => node,
+            None => return,
+        };
+        if let Ok((_, Value::Object(object))) =
+            PodDeserializer::deserialize_any_from(pod.as_bytes())
+        {
+            for property in object.properties {
+                match (property.key, property.value) {
+                    (
+                        pw::spa::sys::SPA_PROP_channelVolumes,
+                        Value::ValueArray(ValueArray::Float(volumes)),
+                    ) => node.volumes = volumes,
+                    (
+                        pw::spa::sys::SPA_PROP_channelMap,
+                        Value::ValueArray(ValueArray::Id(channels)),
+                    ) => node.channels = channels.into_iter().map(|Id(c)| c).collect(),
+                    (pw::spa::sys::SPA_PROP_mute, Value::Bool(mute)) => node.mute = mute,
+                    _ => {}
+                }
+            }
+            let kind = node.kind.object_kind();
+            self.changed(Change::Changed(kind, id));
+        }
+    }
+
+    fn update_card(&mut self, id: u32, param_type: ParamType, pod: &Pod) {
+        let card = match self.cards.get_mut(&id) {
+            Some(card) => card,
+            None => return,
+        };
+        if let Ok((_, Value::Object(object))) =
+            PodDeserializer::deserialize_any_from(pod.as_bytes())
+        {
+            card.update_param(param_type, object);
+            self.changed(Change::Changed(ObjectKind::Card, id));
+            // the ports of the sinks and sources are those of their card
+            let nodes: Vec<_> = self
+                .nodes
+                .iter()
+                .filter(|(_, node)| node.card == Some(id) && node.kind != NodeKind::Stream)
+                .map(|(node_id, node)| Change::Changed(node.kind.object_kind(), *node_id))
+                .collect();
+            for change in nodes {
+                self.changed(change);
+            }
+        }
+    }
+
+    fn ports(&self, node: &NodeState) -> (Vec<PortInfo>, Option<String>) {
+        let direction = match node.kind {
+            NodeKind::Sink => Direction::Output,
+            NodeKind::Source => Direction::Input,
+            NodeKind::Stream => return (Vec::new(), None),
+        };
+        let (card, card_device) = match (
+            node.card.and_then(|card| self.cards.get(&card)),
+            node.card_device,
+        ) {
+            (Some(card), Some(card_device)) => (card, card_device),
+            _ => return (Vec::new(), None),
+        };
+        let ports = card
+            .ports(direction, card_device)
+            .map(|(_, route)| route.port.clone())
+            .collect();
+        let active_port = card
+            .active_routes
+            .get(&card_device)
+            .and_then(|index| card.routes.get(index))
+            .map(|route| route.port.name.clone());
+        (ports, active_port)
+    }
+
+    fn find(&self, kind: NodeKind, name: &str) -> Option<u32> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| node.kind == kind && node.name.as_deref() == Some(name))
+            .map(|(id, _)| *id)
+    }
+
+    fn device_info(&self, id: u32, node: &NodeState) -> DeviceInfo {
+        let (ports, active_port) = self.ports(node);
+        DeviceInfo {
+            name: node.name.clone(),
+            description: node.description.clone(),
+            volume: channel_volumes(&node.volumes),
+            channel_map: channel_map(&node.channels),
+            mute: node.mute,
+            index: id,
+            card: node.card,
+            ports,
+            active_port,
+        }
+    }
+
+    fn device(&self, kind: NodeKind, id: u32) -> Option<DeviceInfo> {
+        self.nodes
+            .get(&id)
+            .filter(|node| node.kind == kind)
+            .map(|node| self.device_info(id, node))
+    }
+
+    fn devices(&self, kind: NodeKind) -> Vec<DeviceInfo> {
+        let mut devices: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.kind == kind)
+            .map(|(id, node)| self.device_info(*id, node))
+            .collect();
+        devices.sort_by_key(|device| device.index);
+        devices
+    }
+
+    fn stream_info(&self, id: u32, node: &NodeState) -> StreamInfo {
+        StreamInfo {
+            index: id,
+            name: node.app_name.clone().or_else(|| node.description.clone()),
+            icon_name: node.icon_name.clone(),
+            volume: channel_volumes(&node.volumes),
+            mute: node.mute,
+            // streams which are not linked yet have no sink
+            sink: self
+                .links
+                .values()
+                .find(|(output, input)| {
+                    *output == id
+                        && self
+                            .nodes
+                            .get(input)
+                            .map_or(false, |node| node.kind == NodeKind::Sink)
+                })
+                .map_or(u32::MAX, |(_, input)| *input),
+        }
+    }
+
+    fn card_info(id: u32, card: &CardState) -> CardInfo {
+        CardInfo {
+            index: id,
+            name: card.name.clone(),
+            description: card.description.clone(),
+            profiles: card.profiles.values().cloned().collect(),
+            active_profile: card
+                .active_profile
+                .and_then(|index| card.profiles.get(&index))
+                .map(|profile| profile.name.clone()),
+        }
+    }
+
+    fn default_device(&self, kind: NodeKind, name: Option<&String>) -> Option<DeviceInfo> {
+        let name = name?;
+        self.devices(kind)
+            .into_iter()
+            .find(|device| device.name.as_ref() == Some(name))
+    }
+}
+
+// Proxies have to be kept alive to receive their events,
+// the listener is dropped first so it is removed from a live proxy
+struct BoundNode {
+    _listener: NodeListener,
+    node: Node,
+}
+
+struct BoundDevice {
+    _listener: DeviceListener,
+    device: Device,
+}
+
+struct BoundMetadata {
+    _listener: MetadataListener,
+    metadata: Metadata,
+}
+
+#[derive(Default)]
+struct Proxies {
+    nodes: HashMap<u32, BoundNode>,
+    devices: HashMap<u32, BoundDevice>,
+    metadata: Option<BoundMetadata>,
+}
+
+pub struct PipeWireServer {
+    // fields are dropped in order, listeners before the objects they are attached to
+    _registry_listener: pw::registry::Listener,
+    _core_listener: pw::core::Listener,
+    proxies: Rc<RefCell<Proxies>>,
+    graph: Rc<RefCell<Graph>>,
+    _registry: Rc<Registry>,
+    core: Core,
+    _context: Context,
+    mainloop: MainLoop,
+}
+
+impl PipeWireServer {
+    // fails when no PipeWire daemon is running
+    pub fn connect() -> Result<PipeWireServer, ServerError> {
+        pw::init();
+        let mainloop = MainLoop::new(None).map_err(|e| ServerError::PipeWire(e.to_string()))?;
+        let context = Context::new(&mainloop).map_err(|e| ServerError::PipeWire(e.to_string()))?;
+        let core = context
+            .connect(None)
+            .map_err(|e| ServerError::PipeWire(e.to_string()))?;
+        let registry = Rc::new(
+            core.get_registry()
+                .map_err(|e| ServerError::PipeWire(e.to_string()))?,
+        );
+        let graph = Rc::new(RefCell::new(Graph::default()));
+        let proxies = Rc::new(RefCell::new(Proxies::default()));
+
+        let core_listener = core
+            .add_listener_local()
+            .done({
+                let graph = graph.clone();
+                move |id, seq| {
+                    if id == PW_ID_CORE {
+                        graph.borrow_mut().done = Some(seq);
+                    }
+                }
+            })
+            .error({
+                let graph = graph.clone();
+                move |id, _, _, message| {
+                    if id == PW_ID_CORE {
+                        graph.borrow_mut().error = Some(message.to_string());
+                    }
+                }
+            })
+            .register();
+
+        let registry_listener = registry
+            .add_listener_local()
+            .global({
+                let graph = graph.clone();
+                let proxies = proxies.clone();
+                let registry = Rc::downgrade(&registry);
+                move |global| {
+                    let (props, registry) = match (global.props, registry.upgrade()) {
+                        (Some(props), Some(registry)) => (props, registry),
+                        _ => return,
+                    };
+                    match global.type_ {
+                        ObjectType::Node => {
+                            let kind = match props
+                                .get("media.class")
+                                .and_then(NodeKind::from_media_class)
+                            {
+                                Some(kind) => kind,
+                                None => return,
+                            };
+                            let node: Node = match registry.bind(global) {
+                                Ok(node) => node,
+                                Err(_) => return,
+                            };
+                            let id = global.id;
+                            let listener = node
+                                .add_listener_local()
+                                .param({
+                                    let graph = graph.clone();
+                                    move |_, param_type, _, _, param| {
+                                        if let (ParamType::Props, Some(pod)) = (param_type, param) {
+                                            graph.borrow_mut().update_props(id, pod);
+                                        }
+                                    }
+                                })
+                                .register();
+                            node.subscribe_params(&[ParamType::Props]);
+
+                            let mut graph = graph.borrow_mut();
+                            graph.changed(Change::Changed(kind.object_kind(), id));
+                            graph.nodes.insert(
+                                id,
+                                NodeState {
+                                    kind,
+                                    name: props.get("node.name").map(str::to_string),
+                                    description: props
+                                        .get("node.description")
+                                        .or_else(|| props.get("node.nick"))
+                                        .map(str::to_string),
+                                    app_name: props.get("application.name").map(str::to_string),
+                                    icon_name: props
+                                        .get("application.icon_name")
+                                        .map(str::to_string),
+                                    volumes: Vec::new(),
+                                    channels: Vec::new(),
+                                    mute: false,
+                                    card: props.get("device.id").and_then(|id| id.parse().ok()),
+                                    card_device: props
+                                        .get("card.profile.device")
+                                        .and_then(|device| device.parse().ok()),
+                                },
+                            );
+                            proxies.borrow_mut().nodes.insert(
+                                id,
+                                BoundNode {
+                                    _listener: listener,
+                                    node,
+                                },
+                            );
+                        }
+                        ObjectType::Device if props.get("media.class") == Some("Audio/Device") => {
+                            let device: Device = match registry.bind(global) {
+                                Ok(device) => device,
+                                Err(_) => return,
+                            };
+                            let id = global.id;
+                            let listener = device
+                                .add_listener_local()
+                                .param({
+                                    let graph = graph.clone();
+                                    move |_, param_type, _, _, param| {
+                                        if let Some(pod) = param {
+                                            graph.borrow_mut().update_card(id, param_type, pod);
+                                        }
+                                    }
+                                })
+                                .register();
+                            device.subscribe_params(&[
+                                ParamType::EnumProfile,
+                                ParamType::Profile,
+                                ParamType::EnumRoute,
+                                ParamType::Route,
+                            ]);
+
+                            let mut graph = graph.borrow_mut();
+                            graph.changed(Change::Changed(ObjectKind::Card, id));
+                            graph.cards.insert(
+                                id,
+                                CardState {
+                                    name: props.get("device.name").map(str::to_string),
+                                    description: props
+                                        .get("device.description")
+                                        .or_else(|| props.get("device.nick"))
+                                        .map(str::to_string),
+                                    ..Default::default()
+                                },
+                            );
+                            proxies.borrow_mut().devices.insert(
+                                id,
+                                BoundDevice {
+                                    _listener: listener,
+                                    device,
+                                },
+                            );
+                        }
+                        ObjectType::Link => {
+                            let output =
+                                props.get("link.output.node").and_then(|id| id.parse().ok());
+                            let input = props.get("link.input.node").and_then(|id| id.parse().ok());
+                            if let (Some(output), Some(input)) = (output, input) {
+                                let mut graph = graph.borrow_mut();
+                                graph.links.insert(global.id, (output, input));
+                                // the sink of a stream is the node it is linked to
+                                graph.changed(Change::Changed(ObjectKind::SinkInput, output));
+                            }
+                        }
+                        ObjectType::Metadata if props.get("metadata.name") == Some("default") => {
+                            let metadata: Metadata = match registry.bind(global) {
+                                Ok(metadata) => metadata,
+                                Err(_) => return,
+                            };
+                            let listener = metadata
+                                .add_listener_local()
+                                .property({
+                                    let graph = graph.clone();
+                                    move |subject, key, _, value| {
+                                        if subject == PW_ID_CORE {
+                                            let mut graph = graph.borrow_mut();
+                                            match key {
+                                                Some("default.audio.sink") => {
+                                                    graph.default_sink =
+                                                        value.and_then(metadata_name);
+                                                    graph.changed(Change::Server);
+                                                }
+                                                Some("default.audio.source") => {
+                                                    graph.default_source =
+                                                        value.and_then(metadata_name);
+                                                    graph.changed(Change::Server);
+                                                }
+                                                _ => {}
+                                            }
+                                        }
+                                        0
+                                    }
+                                })
+                                .register();
+                            proxies.borrow_mut().metadata.replace(BoundMetadata {
+                                _listener: listener,
+                                metadata,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+            })
+            .global_remove({
+                let graph = graph.clone();
+                let proxies = proxies.clone();
+                move |id| {
+                    let mut graph = graph.borrow_mut();
+                    if let Some(node) = graph.nodes.remove(&id) {
+                        graph.changed(Change::Removed(node.kind.object_kind(), id));
+                        proxies.borrow_mut().nodes.remove(&id);
+                    }
+                    if let Some((output, _)) = graph.links.remove(&id) {
+                        graph.changed(Change::Changed(ObjectKind::SinkInput, output));
+                    }
+                    if graph.cards.remove(&id).is_some() {
+                        graph.changed(Change::Removed(ObjectKind::Card, id));
+                        proxies.borrow_mut().devices.remove(&id);
+                    }
+                }
+            })
+            .register();
+
+        let server = PipeWireServer {
+            _registry_listener: registry_listener,
+            _core_listener: core_listener,
+            proxies,
+            graph,
+            _registry: registry,
+            core,
+            _context: context,
+            mainloop,
+        };
+        // the first roundtrip lists the globals, the second one their params
+        server.roundtrip()?;
+        server.roundtrip()?;
+        Ok(server)
+    }
+
+    // Wait for the server to process all the requests sent so far
+    fn roundtrip(&self) -> Result<(), ServerError> {
+        let pending = self
+            .core
+            .sync(0)
+            .map_err(|e| ServerError::PipeWire(e.to_string()))?;
+        let deadline = Instant::now() + ROUNDTRIP_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(ServerError::Misc("roundtrip(): timed out"));
+            }
+            if self.mainloop.loop_().iterate(timeout) < 0 {
+                return Err(ServerError::Misc(
+                    "roundtrip(): failed to iterate main loop",
+                ));
+            }
+            let graph = self.graph.borrow();
+            if let Some(error) = graph.error.as_ref() {
+                return Err(ServerError::PipeWire(error.clone()));
+            }
+            if graph.done == Some(pending) {
+                return Ok(());
+            }
+        }
+    }
+
+    // The volume of a device belongs to its active card route, which is also
+    // where it is saved and restored from, nodes without a card have their own
+    fn set_device_props(&mut self, kind: NodeKind, name: &str, properties: Vec<Property>) {
+        let (id, route) = {
+            let graph = self.graph.borrow();
+            let id = match graph.find(kind, name) {
+                Some(id) => id,
+                None => return,
+            };
+            let route = graph
+                .nodes
+                .get(&id)
+                .and_then(|node| Some((node.card?, node.card_device?)))
+                .and_then(|(card, card_device)| {
+                    let index = graph.cards.get(&card)?.active_routes.get(&card_device)?;
+                    Some((card, card_device, *index))
+                });
+            (id, route)
+        };
+        match route {
+            Some((card, card_device, index)) => self.set_card_param(
+                card,
+                SpaTypes::ObjectParamRoute,
+                ParamType::Route,
+                vec![
+                    Property::new(pw::spa::sys::SPA_PARAM_ROUTE_index, Value::Int(index)),
+                    Property::new(
+                        pw::spa::sys::SPA_PARAM_ROUTE_device,
+                        Value::Int(card_device),
+                    ),
+                    Property::new(
+                        pw::spa::sys::SPA_PARAM_ROUTE_props,
+                        Value::Object(Object {
+                            type_: SpaTypes::ObjectParamProps.as_raw(),
+                            id: ParamType::Route.as_raw(),
+                            properties,
+                        }),
+                    ),
+                    Property::new(pw::spa::sys::SPA_PARAM_ROUTE_save, Value::Bool(true)),
+                ],
+            ),
+            None => self.set_node_props(id, properties),
+        }
+    }
+
+    fn set_node_props(&mut self, id: u32, properties: Vec<Property>) {
+        let bytes = match serialize(SpaTypes::ObjectParamProps, ParamType::Props, properties) {
+            Some(bytes) => bytes,
+            None => return,
+        };
+        if let (Some(bound), Some(pod)) = (
+            self.proxies.borrow().nodes.get(&id),
+            Pod::from_bytes(&bytes),
+        ) {
+            bound.node.set_param(ParamType::Props, 0, pod);
+        }
+        self.roundtrip().ok();
+    }
+
+    fn set_card_param(
+        &mut self,
+        id: u32,
+        object_type: SpaTypes,
+        param_type: ParamType,
+        properties: Vec<Property>,
+    ) {
+        let bytes = match serialize(object_type, param_type, properties) {
+            Some(bytes) => bytes,
+            None => return,
+        };
+        if let (Some(bound), Some(pod)) = (
+            self.proxies.borrow().devices.get(&id),
+            Pod::from_bytes(&bytes),
+        ) {
+            bound.device.set_param(param_type, 0, pod);
+        }
+        self.roundtrip().ok();
+    }
+
+    fn set_port(&mut self, kind: NodeKind, name: &str, port: &str) {
+        let direction = match kind {
+            NodeKind::Sink => Direction::Output,
+            _ => Direction::Input,
+        };
+        let route = {
+            let graph = self.graph.borrow();
+            graph
+                .find(kind, name)
+                .and_then(|id| graph.nodes.get(&id))
+                .and_then(|node| Some((node.card?, node.card_device?)))
+                .and_then(|(card, card_device)| {
+                    graph
+                        .cards
+                        .get(&card)?
+                        .ports(direction, card_device)
+                        .find(|(_, route)| route.port.name == port)
+                        .map(|(index, _)| (card, card_device, *index))
+                })
+        };
+        if let Some((card, card_device, index)) = route {
+            self.set_card_param(
+                card,
+                SpaTypes::ObjectParamRoute,
+                ParamType::Route,
+                vec![
+                    Property::new(pw::spa::sys::SPA_PARAM_ROUTE_index, Value::Int(index)),
+                    Property::new(
+                        pw::spa::sys::SPA_PARAM_ROUTE_device,
+                        Value::Int(card_device),
+                    ),
+                    Property::new(pw::spa::sys::SPA_PARAM_ROUTE_save, Value::Bool(true)),
+                ],
+            );
+        }
+    }
+
+    fn set_default(&mut self, key: &str, name: &str) -> bool {
+        match self.proxies.borrow().metadata.as_ref() {
+            Some(bound) => bound.metadata.set_property(
+                PW_ID_CORE,
+                key,
+                Some("Spa:String:JSON"),
+                Some(
+                    &serde_json::to_string(&MetadataName {
+                        name: Cow::Borrowed(name),
+                    })
+                    .unwrap(),
+                ),
+            ),
+            None => return false,
+        }
+        self.roundtrip().is_ok()
+    }
+}
+
+impl AudioServer for PipeWireServer {
+    fn get_server_info(&mut self) -> Result<ServerInfo, ServerError> {
+        self.roundtrip()?;
+        let graph = self.graph.borrow();
+        Ok(ServerInfo {
+            user_name: None,
+            host_name: None,
+            server_version: None,
+            server_name: Some(String::from("PipeWire")),
+            default_sink_name: graph.default_sink.clone(),
+            default_source_name: graph.default_source.clone(),
+            cookie: 0,
+        })
+    }
+
+    fn get_sinks(&mut self) -> Result<Vec<DeviceInfo>, ServerError> {
+        Ok(self.graph.borrow().devices(NodeKind::Sink))
+    }
+
+    fn get_sources(&mut self) -> Result<Vec<DeviceInfo>, ServerError> {
+        Ok(self.graph.borrow().devices(NodeKind::Source))
+    }
+
+    fn get_sink_input_list(&mut self) -> Result<Vec<StreamInfo>, ServerError> {
+        let graph = self.graph.borrow();
+        let mut streams: Vec<_> = graph
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.kind == NodeKind::Stream)
+            .map(|(id, node)| graph.stream_info(*id, node))
+            .collect();
+        streams.sort_by_key(|stream| stream.index);
+        Ok(streams)
+    }
+
+    fn get_cards(&mut self) -> Result<Vec<CardInfo>, ServerError> {
+        let graph = self.graph.borrow();
+        let mut cards: Vec<_> = graph
+            .cards
+            .iter()
+            .map(|(id, card)| Graph::card_info(*id, card))
+            .collect();
+        cards.sort_by_key(|card| card.index);
+        Ok(cards)
+    }
+
+    fn get_sink(&mut self, index: u32) -> Result<DeviceInfo, ServerError> {
+        self.graph
+            .borrow()
+            .device(NodeKind::Sink, index)
+            .ok_or(ServerError::Misc("get_sink(): no such sink"))
+    }
+
+    fn get_source(&mut self, index: u32) -> Result<DeviceInfo, ServerError> {
+        self.graph
+            .borrow()
+            .device(NodeKind::Source, index)
+            .ok_or(ServerError::Misc("get_source(): no such source"))
+    }
+
+    fn get_sink_input(&mut self, index: u32) -> Result<StreamInfo, ServerError> {
+        let graph = self.graph.borrow();
+        graph
+            .nodes
+            .get(&index)
+            .filter(|node| node.kind == NodeKind::Stream)
+            .map(|node| graph.stream_info(index, node))
+            .ok_or(ServerError::Misc("get_sink_input(): no such stream"))
+    }
+
+    fn get_card(&mut self, index: u32) -> Result<CardInfo, ServerError> {
+        self.graph
+            .borrow()
+            .cards
+            .get(&index)
+            .map(|card| Graph::card_info(index, card))
+            .ok_or(ServerError::Misc("get_card(): no such card"))
+    }
+
+    fn get_default_sink(&mut self) -> Result<DeviceInfo, ServerError> {
+        let graph = self.graph.borrow();
+        graph
+            .default_device(NodeKind::Sink, graph.default_sink.as_ref())
+            .ok_or(ServerError::Misc("get_default_sink() failed"))
+    }
+
+    fn get_default_source(&mut self) -> Result<DeviceInfo, ServerError> {
+        let graph = self.graph.borrow();
+        graph
+            .default_device(NodeKind::Source, graph.default_source.as_ref())
+            .ok_or(ServerError::Misc("get_default_source() failed"))
+    }
+
+    // streams without a target of their own follow the default device
+    fn set_default_sink(&mut self, sink: &str) -> bool {
+        self.set_default("default.configured.audio.sink", sink)
+    }
+
+    fn set_default_source(&mut self, source: &str) -> bool {
+        self.set_default("default.configured.audio.source", source)
+    }
+
+    fn set_card_profile(&mut self, index: u32, profile: &str) {
+        let profile = self.graph.borrow().cards.get(&index).and_then(|card| {
+            card.profiles
+                .iter()
+                .find(|(_, p)| p.name == profile)
+                .map(|(i, _)| *i)
+        });
+        if let Some(profile) = profile {
+            self.set_card_param(
+                index,
+                SpaTypes::ObjectParamProfile,
+                ParamType::Profile,
+                vec![
+                    Property::new(pw::spa::sys::SPA_PARAM_PROFILE_index, Value::Int(profile)),
+                    Property::new(pw::spa::sys::SPA_PARAM_PROFILE_save, Value::Bool(true)),
+                ],
+            );
+        }
+    }
+
+    fn set_sink_port_by_name(&mut self, name: &str, port: &str) {
+        self.set_port(NodeKind::Sink, name, port);
+    }
+
+    fn set_source_port_by_name(&mut self, name: &str, port: &str) {
+        self.set_port(NodeKind::Source, name, port);
+    }
+
+    fn set_sink_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes) {
+        self.set_device_props(NodeKind::Sink, name, vec![volume_property(volume)]);
+    }
+
+    fn set_source_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes) {
+        self.set_device_props(NodeKind::Source, name, vec![volume_property(volume)]);
+    }
+
+    fn set_sink_mute_by_name(&mut self, name: &str, mute: bool) {
+        self.set_device_props(NodeKind::Sink, name, vec![mute_property(mute)]);
+    }
+
+    fn set_source_mute_by_name(&mut self, name: &str, mute: bool) {
+        self.set_device_props(NodeKind::Source, name, vec![mute_property(mute)]);
+    }
+
+    fn set_sink_input_volume(&mut self, index: u32, volume: &ChannelVolumes) {
+        self.set_node_props(index, vec![volume_property(volume)]);
+    }
+
+    fn set_sink_input_mute(&mut self, index: u32, mute: bool) {
+        self.set_node_props(index, vec![mute_property(mute)]);
+    }
+
+    fn move_sink_input(&mut self, index: u32, sink: &str) {
+        if let Some(bound) = self.proxies.borrow().metadata.as_ref() {
+            bound
+                .metadata
+                .set_property(index, "target.object", None, Some(sink));
+        }
+        self.roundtrip().ok();
+    }
+
+    fn event_fd(&self) -> RawFd {
+        self.mainloop.loop_().fd().as_raw_fd()
+    }
+
+    fn dispatch(&mut self) -> Result<Vec<Change>, ServerError> {
+        loop {
+            match self.mainloop.loop_().iterate(Duration::ZERO) {
+                0 => break,
+                n if n < 0 => {
+                    return Err(ServerError::Misc("dispatch(): failed to iterate main loop"))
+                }
+                _ => {}
+            }
+        }
+        let mut graph = self.graph.borrow_mut();
+        if let Some(error) = graph.error.as_ref() {
+            return Err(ServerError::PipeWire(error.clone()));
+        }
+        Ok(std::mem::take(&mut graph.changes))
+    }
+}
+
+fn parse_profile(object: Object) -> Option<(i32, ProfileInfo)> {
+    let mut index = None;
+    let mut profile = ProfileInfo {
+        name: String::new(),
+        description: None,
+        available: true,
+    };
+    for property in object.properties {
+        match (property.key, property.value) {
+            (pw::spa::sys::SPA_PARAM_PROFILE_index, Value::Int(i)) => index = Some(i),
+            (pw::spa::sys::SPA_PARAM_PROFILE_name, Value::String(n)) => profile.name = n,
+            (pw::spa::sys::SPA_PARAM_PROFILE_description, Value::String(d)) => {
+                profile.description = Some(d)
+            }
+            (pw::spa::sys::SPA_PARAM_PROFILE_available, Value::Id(Id(a))) => {
+                profile.available = a != pw::spa::sys::SPA_PARAM_AVAILABILITY_no
+            }
+            _ => {}
+        }
+    }
+    Some((index?, profile))
+}
+
+// returns the route index, the card profile device it is active on and the route
+fn parse_route(object: Object) -> Option<(i32, Option<i32>, Route)> {
+    let mut index = None;
+    let mut device = None;
+    let mut route = Route {
+        direction: Direction::Output,
+        port: PortInfo {
+            name: String::new(),
+            description: None,
+            available: true,
+        },
+        devices: Vec::new(),
+        profiles: Vec::new(),
+    };
+    for property in object.properties {
+        match (property.key, property.value) {
+            (pw::spa::sys::SPA_PARAM_ROUTE_index, Value::Int(i)) => index = Some(i),
+            (pw::spa::sys::SPA_PARAM_ROUTE_direction, Value::Id(Id(d))) => {
+                route.direction = Direction::from_raw(d)
+            }
+            (pw::spa::sys::SPA_PARAM_ROUTE_device, Value::Int(d)) => device = Some(d),
+            (pw::spa::sys::SPA_PARAM_ROUTE_name, Value::String(n)) => route.port.name = n,
+            (pw::spa::sys::SPA_PARAM_ROUTE_description, Value::String(d)) => {
+                route.port.description = Some(d)
+            }
+            (pw::spa::sys::SPA_PARAM_ROUTE_available, Value::Id(Id(a))) => {
+                route.port.available = a != pw::spa::sys::SPA_PARAM_AVAILABILITY_no
+            }
+            (pw::spa::sys::SPA_PARAM_ROUTE_devices, Value::ValueArray(ValueArray::Int(d))) => {
+                route.devices = d
+            }
+            (pw::spa::sys::SPA_PARAM_ROUTE_profiles, Value::ValueArray(ValueArray::Int(p))) => {
+                route.profiles = p
+            }
+            _ => {}
+        }
+    }
+    Some((index?, device, route))
+}
+
+fn serialize(
+    object_type: SpaTypes,
+    param_type: ParamType,
+    properties: Vec<Property>,
+) -> Option<Vec<u8>> {
+    let object = Value::Object(Object {
+        type_: object_type.as_raw(),
+        id: param_type.as_raw(),
+        properties,
+    });
+    PodSerializer::serialize(Cursor::new(Vec::new()), &object)
+        .ok()
+        .map(|(cursor, _)| cursor.into_inner())
+}
+
+// PipeWire volumes are linear, PulseAudio volumes are cubic
+fn channel_volumes(volumes: &[f32]) -> ChannelVolumes {
+    let mut channel_volumes = ChannelVolumes::default();
+    channel_volumes.set_len(volumes.len() as u8);
+    for (channel_volume, volume) in channel_volumes.get_mut().iter_mut().zip(volumes) {
+        *channel_volume = VolumeLinear(*volume as f64).into();
+    }
+    channel_volumes
+}
+
+// the map is invalid, so it can't be balanced, if a position has no PulseAudio equivalent
+fn channel_map(channels: &[u32]) -> channelmap::Map {
+    let mut map = channelmap::Map::default();
+    map.set_len(channels.len() as u8);
+    for (position, channel) in map.get_mut().iter_mut().zip(channels) {
+        *position = match *channel {
+            pw::spa::sys::SPA_AUDIO_CHANNEL_MONO => Position::Mono,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_FL => Position::FrontLeft,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_FR => Position::FrontRight,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_FC => Position::FrontCenter,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_LFE => Position::Lfe,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_SL => Position::SideLeft,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_SR => Position::SideRight,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_FLC => Position::FrontLeftOfCenter,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_FRC => Position::FrontRightOfCenter,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_RC => Position::RearCenter,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_RL => Position::RearLeft,
+            pw::spa::sys::SPA_AUDIO_CHANNEL_RR => Position::RearRight,
+            _ => Position::Invalid,
+        };
+    }
+    map
+}
+
+fn volume_property(volume: &ChannelVolumes) -> Property {
+    Property::new(
+        pw::spa::sys::SPA_PROP_channelVolumes,
+        Value::ValueArray(ValueArray::Float(
+            volume
+                .get()
+                .iter()
+                .map(|v| VolumeLinear::from(*v).0 as f32)
+                .collect(),
+        )),
+    )
+}
+
+fn mute_property(mute: bool) -> Property {
+    Property::new(pw::spa::sys::SPA_PROP_mute, Value::Bool(mute))
+}
+
+// default metadata values are JSON objects like `{ "name": "alsa_output.pci-0000_00_1f.3.analog-stereo" }`
+#[derive(Serialize, Deserialize)]
+struct MetadataName<'a> {
+    #[serde(borrow)]
+    name: Cow<'a, str>,
+}
+
+// Read the name out of a `{ "name": "..." }` metadata value
+fn metadata_name(value: &str) -> Option<String> {
+    serde_json::from_str::<MetadataName>(value)
+        .ok()
+        .map(|value| value.name.into_owned())
+}
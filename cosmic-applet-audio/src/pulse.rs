@@ -1,20 +1,34 @@
 use cosmic::iced_native::subscription::{self, Subscription};
 use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::os::unix::{
+    io::{AsRawFd, RawFd},
+    net::UnixStream,
+};
 use std::{rc::Rc, thread};
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 extern crate libpulse_binding as pulse;
 //use futures::channel::mpsc;
+use crate::server::{self, push_change, AudioServer, Change, ObjectKind, ServerError};
 use libpulse_binding::{
     callbacks::ListResult,
+    channelmap,
     context::{
-        introspect::{Introspector, SinkInfo, SourceInfo},
+        introspect::{
+            CardProfileInfo, Introspector, SinkInfo, SinkInputInfo, SinkPortInfo, SourceInfo,
+            SourcePortInfo,
+        },
+        subscribe::{Facility, InterestMaskSet, Operation},
         Context,
     },
-    error::PAErr,
-    mainloop::standard::{IterateResult, Mainloop},
+    def::PortAvailable,
+    mainloop::threaded::Mainloop,
     proplist::Proplist,
     volume::ChannelVolumes,
 };
+
 pub fn connect() -> Subscription<Event> {
     struct Connect;
 
@@ -52,27 +66,14 @@ pub fn connect() -> Subscription<Event> {
                 State::Connected(mut from_pulse) => {
                     // This is where we match messages from the pulse server to pass to the gui
                     match from_pulse.recv().await {
-                        Some(Message::SetSinks(sinks)) => (
-                            Some(Event::MessageReceived(Message::SetSinks(sinks))),
-                            State::Connected(from_pulse),
-                        ),
-                        Some(Message::SetSources(sources)) => (
-                            Some(Event::MessageReceived(Message::SetSources(sources))),
-                            State::Connected(from_pulse),
-                        ),
-                        Some(Message::SetDefaultSink(sink)) => (
-                            Some(Event::MessageReceived(Message::SetDefaultSink(sink))),
-                            State::Connected(from_pulse),
-                        ),
-                        Some(Message::SetDefaultSource(source)) => (
-                            Some(Event::MessageReceived(Message::SetDefaultSource(source))),
-                            State::Connected(from_pulse),
-                        ),
                         Some(Message::Disconnected) => {
                             (Some(Event::Disconnected), State::Connecting(from_pulse))
                         }
                         None => (Some(Event::Disconnected), State::Connecting(from_pulse)),
-                        _ => (None, State::Connected(from_pulse)),
+                        Some(message) => (
+                            Some(Event::MessageReceived(message)),
+                            State::Connected(from_pulse),
+                        ),
                     }
                 }
             }
@@ -83,8 +84,8 @@ pub fn connect() -> Subscription<Event> {
 // #[derive(Debug)]
 enum State {
     Init,
-    Connecting(tokio::sync::mpsc::Receiver<Message>),
-    Connected(tokio::sync::mpsc::Receiver<Message>),
+    Connecting(UnboundedReceiver<Message>),
+    Connected(UnboundedReceiver<Message>),
 }
 
 #[derive(Debug, Clone)]
@@ -96,13 +97,15 @@ pub enum Event {
 }
 
 #[derive(Debug, Clone)]
-pub struct Connection(tokio::sync::mpsc::Sender<Message>);
+pub struct Connection(UnboundedSender<Message>);
 
 impl Connection {
+    // The queue is unbounded so dragging a slider can't fill it up,
+    // the server thread skips volume changes that were overtaken already
     pub fn send(&mut self, message: Message) {
-        self.0
-            .try_send(message)
-            .expect("Send message to PulseAudio server");
+        if self.0.send(message).is_err() {
+            log::error!("PulseAudio server thread is gone");
+        }
     }
 }
 
@@ -121,21 +124,42 @@ pub enum Message {
     SetDefaultSource(DeviceInfo),
     SetSinkVolumeByName(String, ChannelVolumes),
     SetSourceVolumeByName(String, ChannelVolumes),
+    SetSinkMuteByName(String, bool),
+    SetSourceMuteByName(String, bool),
+    GetSinkInputs,
+    SetSinkInputs(Vec<StreamInfo>),
+    SetSinkInputVolume(u32, ChannelVolumes),
+    SetSinkInputMute(u32, bool),
+    MoveSinkInput(u32, String),
+    GetCards,
+    SetCards(Vec<CardInfo>),
+    SetCardProfile(u32, String),
+    SetSinkPortByName(String, String),
+    SetSourcePortByName(String, String),
+    // Objects changed outside of the applet
+    SinkChanged(DeviceInfo),
+    SinkRemoved(u32),
+    SourceChanged(DeviceInfo),
+    SourceRemoved(u32),
+    SinkInputChanged(StreamInfo),
+    SinkInputRemoved(u32),
+    CardChanged(CardInfo),
+    CardRemoved(u32),
 }
 
 struct PulseHandle {
-    to_pulse: tokio::sync::mpsc::Sender<Message>,
-    from_pulse: tokio::sync::mpsc::Receiver<Message>,
+    to_pulse: UnboundedSender<Message>,
+    from_pulse: UnboundedReceiver<Message>,
 }
 
 impl PulseHandle {
     // Create pulse server thread, and bidirectional comms
     pub fn new() -> PulseHandle {
-        let (to_pulse, mut to_pulse_recv) = tokio::sync::mpsc::channel(10);
-        let (mut from_pulse_send, from_pulse) = tokio::sync::mpsc::channel(10);
+        let (to_pulse, mut to_pulse_recv) = unbounded_channel();
+        let (mut from_pulse_send, from_pulse) = unbounded_channel();
         // get initial connection status
         to_pulse
-            .try_send(Message::UpdateConnection)
+            .send(Message::UpdateConnection)
             .expect("Failed to send initial connection update message");
         // this thread should complete by pushing a completed message,
         // or fail message. This should never complete/fail without pushing
@@ -152,157 +176,252 @@ impl PulseHandle {
             // this lets us put the thread to sleep, but keep hold a single
             // thread, because pulse audio's API is not multithreaded... at all
             rt.block_on(async {
-                let mut server: Option<PulseServer> = None;
+                let mut server: Option<Box<dyn AudioServer>> = None;
+                // readable when the server has events, so the thread sleeps until
+                // either the GUI or the server has something for it
+                let mut events: Option<AsyncFd<EventFd>> = None;
+                // a message taken off the queue while skipping volume changes
+                let mut next: Option<Message> = None;
 
                 loop {
                     // This is where the we match messages from the GUI to pass to the pulse server
-                    if let Some(msg) = to_pulse_recv.recv().await {
-                        match msg {
-                            Message::GetDefaultSink => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                match server.get_default_sink() {
-                                    Ok(sink) => from_pulse_send
-                                        .send(Message::SetDefaultSink(sink))
-                                        .await
-                                        .unwrap(),
-                                    Err(_) => {
-                                        PulseHandle::send_disconnected(&mut from_pulse_send).await
-                                    }
+                    let msg = match next.take() {
+                        Some(msg) => msg,
+                        None => tokio::select! {
+                            msg = to_pulse_recv.recv() => match msg {
+                                Some(msg) => msg,
+                                None => break,
+                            },
+                            _ = server_events(events.as_ref()) => {
+                                // push changes made outside of the applet to the GUI
+                                PulseHandle::send_changes(
+                                    &mut server,
+                                    &mut events,
+                                    &mut from_pulse_send,
+                                );
+                                continue;
+                            }
+                        },
+                    };
+                    let msg = latest_volume(msg, &mut to_pulse_recv, &mut next);
+                    if let Some(server) = server.as_mut() {
+                        server.lock();
+                    }
+                    match msg {
+                        Message::GetDefaultSink => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            match server.get_default_sink() {
+                                Ok(sink) => {
+                                    from_pulse_send.send(Message::SetDefaultSink(sink)).unwrap()
                                 }
+                                Err(_) => PulseHandle::send_disconnected(&mut from_pulse_send),
                             }
-                            Message::GetDefaultSource => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                match server.get_default_source() {
-                                    Ok(source) => from_pulse_send
-                                        .send(Message::SetDefaultSource(source))
-                                        .await
-                                        .unwrap(),
-                                    Err(e) => {
-                                        log::error!("ERROR! {:?}", e);
-                                        PulseHandle::send_disconnected(&mut from_pulse_send).await;
-                                    }
+                        }
+                        Message::GetDefaultSource => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            match server.get_default_source() {
+                                Ok(source) => from_pulse_send
+                                    .send(Message::SetDefaultSource(source))
+                                    .unwrap(),
+                                Err(e) => {
+                                    log::error!("ERROR! {:?}", e);
+                                    PulseHandle::send_disconnected(&mut from_pulse_send);
                                 }
                             }
-                            Message::GetSinks => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                match server.get_sinks() {
-                                    Ok(sinks) => from_pulse_send
-                                        .send(Message::SetSinks(sinks))
-                                        .await
-                                        .unwrap(),
-                                    Err(_) => {
-                                        PulseHandle::send_disconnected(&mut from_pulse_send).await
-                                    }
+                        }
+                        Message::GetSinks => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            match server.get_sinks() {
+                                Ok(sinks) => {
+                                    from_pulse_send.send(Message::SetSinks(sinks)).unwrap()
                                 }
+                                Err(_) => PulseHandle::send_disconnected(&mut from_pulse_send),
                             }
-                            Message::GetSources => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                match server.get_sources() {
-                                    Ok(sinks) => from_pulse_send
-                                        .send(Message::SetSources(sinks))
-                                        .await
-                                        .unwrap(),
-                                    Err(_) => {
-                                        PulseHandle::send_disconnected(&mut from_pulse_send).await
-                                    }
+                        }
+                        Message::GetSources => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            match server.get_sources() {
+                                Ok(sinks) => {
+                                    from_pulse_send.send(Message::SetSources(sinks)).unwrap()
                                 }
+                                Err(_) => PulseHandle::send_disconnected(&mut from_pulse_send),
                             }
-                            Message::SetSinkVolumeByName(name, channel_volumes) => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                server.set_sink_volume_by_name(&name, &channel_volumes)
+                        }
+                        Message::SetSinkVolumeByName(name, channel_volumes) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_sink_volume_by_name(&name, &channel_volumes)
+                        }
+                        Message::SetSourceVolumeByName(name, channel_volumes) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_source_volume_by_name(&name, &channel_volumes)
+                        }
+                        Message::SetSinkMuteByName(name, mute) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_sink_mute_by_name(&name, mute)
+                        }
+                        Message::SetSourceMuteByName(name, mute) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_source_mute_by_name(&name, mute)
+                        }
+                        Message::GetSinkInputs => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            match server.get_sink_input_list() {
+                                Ok(streams) => from_pulse_send
+                                    .send(Message::SetSinkInputs(streams))
+                                    .unwrap(),
+                                Err(_) => PulseHandle::send_disconnected(&mut from_pulse_send),
                             }
-                            Message::SetSourceVolumeByName(name, channel_volumes) => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                server.set_source_volume_by_name(&name, &channel_volumes)
+                        }
+                        Message::SetSinkInputVolume(index, channel_volumes) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_sink_input_volume(index, &channel_volumes)
+                        }
+                        Message::SetSinkInputMute(index, mute) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_sink_input_mute(index, mute)
+                        }
+                        Message::MoveSinkInput(index, sink) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.move_sink_input(index, &sink)
+                        }
+                        Message::GetCards => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            match server.get_cards() {
+                                Ok(cards) => {
+                                    from_pulse_send.send(Message::SetCards(cards)).unwrap()
+                                }
+                                Err(_) => PulseHandle::send_disconnected(&mut from_pulse_send),
                             }
-                            Message::UpdateConnection => {
-                                log::info!(
-                                    "Updating Connection, server exists: {:?}",
-                                    server.is_some()
-                                );
-                                if let Some(mut cur_server) = server.take() {
-                                    log::trace!("getting server info...");
-                                    if let Err(_) = cur_server.get_server_info() {
-                                        log::warn!("got error, server must be disconnected...");
-                                        PulseHandle::send_disconnected(&mut from_pulse_send).await;
-                                    } else {
-                                        log::trace!("got server info, still connected...");
-                                        server = Some(cur_server);
-                                    }
+                        }
+                        Message::SetCardProfile(index, profile) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_card_profile(index, &profile)
+                        }
+                        Message::SetSinkPortByName(name, port) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_sink_port_by_name(&name, &port)
+                        }
+                        Message::SetSourcePortByName(name, port) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            server.set_source_port_by_name(&name, &port)
+                        }
+                        Message::UpdateConnection => {
+                            log::info!(
+                                "Updating Connection, server exists: {:?}",
+                                server.is_some()
+                            );
+                            if let Some(mut cur_server) = server.take() {
+                                log::trace!("getting server info...");
+                                if let Err(_) = cur_server.get_server_info() {
+                                    log::warn!("got error, server must be disconnected...");
+                                    events = None;
+                                    PulseHandle::send_disconnected(&mut from_pulse_send);
                                 } else {
-                                    match PulseServer::connect().and_then(|server| server.init()) {
-                                        Ok(new_server) => {
-                                            log::info!("Connected to server");
-                                            PulseHandle::send_connected(&mut from_pulse_send).await;
-                                            server = Some(new_server);
-                                        }
-                                        Err(err) => {
-                                            log::error!("Failed to connect to server: {:?}", err);
-                                        }
-                                    }
+                                    log::trace!("got server info, still connected...");
+                                    server = Some(cur_server);
                                 }
-                            }
-                            Message::SetDefaultSink(device) => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                let default_sink = match server.get_default_sink() {
-                                    Ok(sink) => sink,
-                                    Err(_) => continue,
-                                };
-                                let to_move = server.get_sink_inputs(default_sink.index);
-                                if let Some(name) = device.name.as_ref() {
-                                    if server.set_default_sink(name, to_move) {
-                                        from_pulse_send
-                                            .send(Message::SetDefaultSink(device))
-                                            .await
-                                            .unwrap();
+                            } else {
+                                match server::connect() {
+                                    Ok(new_server) => {
+                                        log::info!("Connected to server");
+                                        PulseHandle::send_connected(&mut from_pulse_send);
+                                        events = AsyncFd::new(EventFd(new_server.event_fd()))
+                                            .map_err(|err| {
+                                                log::error!(
+                                                    "Failed to watch server events: {:?}",
+                                                    err
+                                                )
+                                            })
+                                            .ok();
+                                        server = Some(new_server);
+                                    }
+                                    Err(err) => {
+                                        log::error!("Failed to connect to server: {:?}", err);
                                     }
                                 }
                             }
-                            Message::SetDefaultSource(device) => {
-                                let server = match server.as_mut() {
-                                    Some(s) => s,
-                                    None => continue,
-                                };
-                                let default_source = match server.get_default_source() {
-                                    Ok(source) => source,
-                                    Err(_) => continue,
-                                };
-                                let to_move = server.get_source_outputs(default_source.index);
-                                if let Some(name) = device.name.as_ref() {
-                                    if server.set_default_source(name, to_move) {
-                                        from_pulse_send
-                                            .send(Message::SetDefaultSource(device))
-                                            .await
-                                            .unwrap();
-                                    }
+                        }
+                        Message::SetDefaultSink(device) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            if let Some(name) = device.name.as_ref() {
+                                if server.set_default_sink(name) {
+                                    from_pulse_send
+                                        .send(Message::SetDefaultSink(device))
+                                        .unwrap();
                                 }
                             }
-                            _ => {
-                                log::warn!("message doesn't match")
+                        }
+                        Message::SetDefaultSource(device) => {
+                            let server = match server.as_mut() {
+                                Some(s) => s,
+                                None => continue,
+                            };
+                            if let Some(name) = device.name.as_ref() {
+                                if server.set_default_source(name) {
+                                    from_pulse_send
+                                        .send(Message::SetDefaultSource(device))
+                                        .unwrap();
+                                }
                             }
                         }
+                        _ => {
+                            log::warn!("message doesn't match")
+                        }
                     }
+
+                    // changes made while handling the message may not wake the thread up again
+                    PulseHandle::send_changes(&mut server, &mut events, &mut from_pulse_send);
                 }
             });
         });
@@ -312,37 +431,134 @@ impl PulseHandle {
         }
     }
 
-    async fn send_disconnected(sender: &mut tokio::sync::mpsc::Sender<Message>) {
-        sender.send(Message::Disconnected).await.unwrap()
+    fn send_disconnected(sender: &mut UnboundedSender<Message>) {
+        sender.send(Message::Disconnected).unwrap()
     }
 
     #[allow(dead_code)]
-    async fn send_connected(sender: &mut tokio::sync::mpsc::Sender<Message>) {
-        sender.send(Message::Connected).await.unwrap()
+    fn send_connected(sender: &mut UnboundedSender<Message>) {
+        sender.send(Message::Connected).unwrap()
+    }
+
+    // Dispatch the server events and push the objects that changed to the GUI
+    fn send_changes(
+        server: &mut Option<Box<dyn AudioServer>>,
+        events: &mut Option<AsyncFd<EventFd>>,
+        sender: &mut UnboundedSender<Message>,
+    ) {
+        let cur_server = match server.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+        cur_server.lock();
+        let changes = match cur_server.dispatch() {
+            Ok(changes) => changes,
+            Err(_) => {
+                log::warn!("failed to dispatch server events, server must be disconnected...");
+                *events = None;
+                *server = None;
+                PulseHandle::send_disconnected(sender);
+                return;
+            }
+        };
+        for change in changes {
+            let messages = match change {
+                Change::Server => vec![
+                    cur_server.get_default_sink().map(Message::SetDefaultSink),
+                    cur_server
+                        .get_default_source()
+                        .map(Message::SetDefaultSource),
+                ],
+                Change::Changed(kind, index) => vec![match kind {
+                    ObjectKind::Sink => cur_server.get_sink(index).map(Message::SinkChanged),
+                    ObjectKind::Source => cur_server.get_source(index).map(Message::SourceChanged),
+                    ObjectKind::SinkInput => cur_server
+                        .get_sink_input(index)
+                        .map(Message::SinkInputChanged),
+                    ObjectKind::Card => cur_server.get_card(index).map(Message::CardChanged),
+                }],
+                Change::Removed(kind, index) => vec![Ok(match kind {
+                    ObjectKind::Sink => Message::SinkRemoved(index),
+                    ObjectKind::Source => Message::SourceRemoved(index),
+                    ObjectKind::SinkInput => Message::SinkInputRemoved(index),
+                    ObjectKind::Card => Message::CardRemoved(index),
+                })],
+            };
+            // an object can be gone by the time it is fetched, its removal follows
+            for message in messages.into_iter().flatten() {
+                sender.send(message).unwrap();
+            }
+        }
+        cur_server.unlock();
     }
 }
 
-struct PulseServer {
-    mainloop: Rc<RefCell<Mainloop>>,
-    context: Rc<RefCell<Context>>,
-    introspector: Introspector,
+// Skip queued volume changes of an object that are overtaken by a later one,
+// so the server doesn't fall behind a slider drag or fast scrolling
+fn latest_volume(
+    mut msg: Message,
+    rx: &mut UnboundedReceiver<Message>,
+    next: &mut Option<Message>,
+) -> Message {
+    while let Ok(queued) = rx.try_recv() {
+        let overtaken = match (&msg, &queued) {
+            (Message::SetSinkVolumeByName(a, _), Message::SetSinkVolumeByName(b, _))
+            | (Message::SetSourceVolumeByName(a, _), Message::SetSourceVolumeByName(b, _)) => {
+                a == b
+            }
+            (Message::SetSinkInputVolume(a, _), Message::SetSinkInputVolume(b, _)) => a == b,
+            _ => false,
+        };
+        if !overtaken {
+            *next = Some(queued);
+            break;
+        }
+        msg = queued;
+    }
+    msg
+}
+
+// The fd is owned by the server, this only lets tokio wait for it
+struct EventFd(RawFd);
+
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
 }
 
-#[derive(Clone, Debug)]
-enum PulseServerError<'a> {
-    IterateErr(IterateResult),
-    ContextErr(pulse::context::State),
-    OperationErr(pulse::operation::State),
-    PAErr(PAErr),
-    Connect,
-    Misc(&'a str),
+// Wait for the server to have events to dispatch, forever if there is no server
+async fn server_events(events: Option<&AsyncFd<EventFd>>) {
+    match events {
+        Some(events) => {
+            if let Ok(mut guard) = events.readable().await {
+                // the events are dispatched before waiting again
+                guard.clear_ready();
+            }
+        }
+        None => std::future::pending().await,
+    }
+}
+
+// The mainloop runs on its own thread, which is locked out while requests are made.
+// Callbacks run on that thread and only touch state behind the lock.
+pub struct PulseServer {
+    introspector: Introspector,
+    context: Rc<RefCell<Context>>,
+    // dropped after the context and introspector using it
+    mainloop: Rc<RefCell<Mainloop>>,
+    locked: bool,
+    changes: Rc<RefCell<Vec<Change>>>,
+    // the mainloop thread writes to `wakeup` to make `events` readable
+    events: UnixStream,
+    wakeup: UnixStream,
 }
 
 // `PulseServer` code is heavily inspired by Dave Patrick Caberto's pulsectl-rs (SeaDve)
 // https://crates.io/crates/pulsectl-rs
 impl PulseServer {
     // connect() requires init() to be run after
-    pub fn connect() -> Result<PulseServer, PulseServerError<'static>> {
+    pub fn connect() -> Result<PulseServer, ServerError> {
         // TODO: fix app name, should be variable
         let mut proplist = Proplist::new().unwrap();
         proplist
@@ -350,15 +566,13 @@ impl PulseServer {
                 pulse::proplist::properties::APPLICATION_NAME,
                 "com.system76",
             )
-            .or(Err(PulseServerError::Connect))?;
+            .or(Err(ServerError::Connect))?;
 
-        let mainloop = Rc::new(RefCell::new(
-            pulse::mainloop::standard::Mainloop::new().ok_or(PulseServerError::Connect)?,
-        ));
+        let mainloop = Rc::new(RefCell::new(Mainloop::new().ok_or(ServerError::Connect)?));
 
         let context = Rc::new(RefCell::new(
             Context::new_with_proplist(&*mainloop.borrow(), "MainConn", &proplist)
-                .ok_or(PulseServerError::Connect)?,
+                .ok_or(ServerError::Connect)?,
         ));
 
         let introspector = context.borrow_mut().introspect();
@@ -366,46 +580,150 @@ impl PulseServer {
         context
             .borrow_mut()
             .connect(None, pulse::context::FlagSet::NOFLAGS, None)
-            .map_err(PulseServerError::PAErr)?;
+            .map_err(ServerError::PAErr)?;
+
+        let (events, wakeup) = UnixStream::pair().or(Err(ServerError::Connect))?;
+        events.set_nonblocking(true).or(Err(ServerError::Connect))?;
+        wakeup.set_nonblocking(true).or(Err(ServerError::Connect))?;
 
         Ok(PulseServer {
-            mainloop,
-            context,
             introspector,
+            context,
+            mainloop,
+            locked: false,
+            changes: Rc::new(RefCell::new(Vec::new())),
+            events,
+            wakeup,
         })
     }
 
-    // Wait for pulse audio connection to complete
-    pub fn init(self) -> Result<Self, PulseServerError<'static>> {
+    // Start the mainloop thread and wait for pulse audio connection to complete
+    pub fn init(mut self) -> Result<Self, ServerError> {
+        let mainloop = self.mainloop.clone();
+        self.context
+            .borrow_mut()
+            .set_state_callback(Some(Box::new(move || unsafe {
+                (*mainloop.as_ptr()).signal(false);
+            })));
+        self.lock();
+        self.mainloop
+            .borrow_mut()
+            .start()
+            .map_err(ServerError::PAErr)?;
         loop {
-            match self.mainloop.borrow_mut().iterate(false) {
-                IterateResult::Success(_) => {}
-                IterateResult::Err(e) => {
-                    return Err(PulseServerError::IterateErr(IterateResult::Err(e)))
-                }
-                IterateResult::Quit(e) => {
-                    return Err(PulseServerError::IterateErr(IterateResult::Quit(e)))
+            let state = self.context.borrow().get_state();
+            match state {
+                pulse::context::State::Ready => break,
+                pulse::context::State::Failed | pulse::context::State::Terminated => {
+                    return Err(ServerError::ContextErr(state))
                 }
+                _ => self.mainloop.borrow_mut().wait(),
             }
+        }
+        // from now on losing the connection wakes up the pulse thread
+        let wakeup = self.wakeup.try_clone().or(Err(ServerError::Connect))?;
+        self.context
+            .borrow_mut()
+            .set_state_callback(Some(Box::new(move || {
+                let _ = (&wakeup).write(&[0]);
+            })));
+        Ok(self)
+    }
 
-            match self.context.borrow().get_state() {
-                pulse::context::State::Ready => break,
-                pulse::context::State::Failed => {
-                    return Err(PulseServerError::ContextErr(pulse::context::State::Failed))
-                }
-                pulse::context::State::Terminated => {
-                    return Err(PulseServerError::ContextErr(
-                        pulse::context::State::Terminated,
+    // Get notified of changes to devices, streams and defaults
+    pub fn subscribe(&mut self) -> Result<(), ServerError> {
+        let changes = self.changes.clone();
+        let wakeup = self.wakeup.try_clone().or(Err(ServerError::Misc(
+            "subscribe(): failed to clone wakeup",
+        )))?;
+        self.context
+            .borrow_mut()
+            .set_subscribe_callback(Some(Box::new(
+                move |facility: Option<Facility>, operation: Option<Operation>, index: u32| {
+                    let change = match (facility.and_then(object_kind), operation) {
+                        (Some(kind), Some(Operation::Removed)) => Change::Removed(kind, index),
+                        (Some(kind), _) => Change::Changed(kind, index),
+                        (None, _) if matches!(facility, Some(Facility::Server)) => Change::Server,
+                        _ => return,
+                    };
+                    push_change(&mut changes.borrow_mut(), change);
+                    // a full socket already wakes the thread up
+                    let _ = (&wakeup).write(&[0]);
+                },
+            )));
+        let op = self.context.borrow_mut().subscribe(
+            InterestMaskSet::SINK
+                | InterestMaskSet::SOURCE
+                | InterestMaskSet::SERVER
+                | InterestMaskSet::SINK_INPUT
+                | InterestMaskSet::CARD,
+            |_| {},
+        );
+        self.wait_for_result(op)
+    }
+
+    // after building an operation such as get_devices() we need to wait
+    // for the mainloop thread to signal that the operation completed
+    fn wait_for_result<G: ?Sized>(
+        &self,
+        mut operation: pulse::operation::Operation<G>,
+    ) -> Result<(), ServerError> {
+        let mainloop = self.mainloop.clone();
+        operation.set_state_callback(Some(Box::new(move || unsafe {
+            (*mainloop.as_ptr()).signal(false);
+        })));
+        loop {
+            match operation.get_state() {
+                pulse::operation::State::Done => return Ok(()),
+                pulse::operation::State::Running => self.mainloop.borrow_mut().wait(),
+                pulse::operation::State::Cancelled => {
+                    return Err(ServerError::OperationErr(
+                        pulse::operation::State::Cancelled,
                     ))
                 }
-                _ => {}
             }
         }
-        Ok(self)
     }
 
-    // Get a list of output devices
-    pub fn get_sinks(&self) -> Result<Vec<DeviceInfo>, PulseServerError> {
+    fn get_source_outputs(&mut self, source: u32) -> Vec<u32> {
+        let result = Rc::new(RefCell::new(Vec::new()));
+        let result_ref = result.clone();
+        let op = self.introspector.get_source_output_info_list(move |list| {
+            if let ListResult::Item(item) = list {
+                if source == item.source {
+                    result.borrow_mut().push(item.index);
+                }
+            }
+        });
+        self.wait_for_result(op).ok();
+        result_ref.replace(Vec::new())
+    }
+
+    fn get_sink_inputs(&mut self, sink: u32) -> Vec<u32> {
+        self.get_sink_input_list()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|stream| stream.sink == sink)
+            .map(|stream| stream.index)
+            .collect()
+    }
+}
+
+impl AudioServer for PulseServer {
+    fn get_server_info(&mut self) -> Result<ServerInfo, ServerError> {
+        let info = Rc::new(RefCell::new(Some(None)));
+        let info_ref = info.clone();
+
+        let op = self.introspector.get_server_info(move |res| {
+            info_ref.borrow_mut().as_mut().unwrap().replace(res.into());
+        });
+        self.wait_for_result(op)?;
+        info.take()
+            .flatten()
+            .ok_or(ServerError::Misc("get_server_info(): failed"))
+    }
+
+    fn get_sinks(&mut self) -> Result<Vec<DeviceInfo>, ServerError> {
         let list: Rc<RefCell<Option<Vec<DeviceInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
         let list_ref = list.clone();
 
@@ -417,14 +735,13 @@ impl PulseServer {
             },
         );
         self.wait_for_result(operation).and_then(|_| {
-            list.borrow_mut().take().ok_or(PulseServerError::Misc(
+            list.borrow_mut().take().ok_or(ServerError::Misc(
                 "get_sinks(): failed to wait for operation",
             ))
         })
     }
 
-    // Get a list of input devices
-    pub fn get_sources(&self) -> Result<Vec<DeviceInfo>, PulseServerError> {
+    fn get_sources(&mut self) -> Result<Vec<DeviceInfo>, ServerError> {
         let list: Rc<RefCell<Option<Vec<DeviceInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
         let list_ref = list.clone();
 
@@ -436,26 +753,153 @@ impl PulseServer {
             },
         );
         self.wait_for_result(operation).and_then(|_| {
-            list.borrow_mut().take().ok_or(PulseServerError::Misc(
+            list.borrow_mut().take().ok_or(ServerError::Misc(
                 "get_sources(): Failed to wait for operation",
             ))
         })
     }
 
-    pub fn get_server_info(&mut self) -> Result<ServerInfo, PulseServerError> {
-        let info = Rc::new(RefCell::new(Some(None)));
-        let info_ref = info.clone();
+    fn get_sink_input_list(&mut self) -> Result<Vec<StreamInfo>, ServerError> {
+        let list: Rc<RefCell<Option<Vec<StreamInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
+        let list_ref = list.clone();
 
-        let op = self.introspector.get_server_info(move |res| {
-            info_ref.borrow_mut().as_mut().unwrap().replace(res.into());
-        });
+        let operation = self.introspector.get_sink_input_info_list(
+            move |input_list: ListResult<&SinkInputInfo>| {
+                if let ListResult::Item(item) = input_list {
+                    list_ref.borrow_mut().as_mut().unwrap().push(item.into());
+                }
+            },
+        );
+        self.wait_for_result(operation).and_then(|_| {
+            list.borrow_mut().take().ok_or(ServerError::Misc(
+                "get_sink_input_list(): failed to wait for operation",
+            ))
+        })
+    }
+
+    fn get_sink(&mut self, index: u32) -> Result<DeviceInfo, ServerError> {
+        let device = Rc::new(RefCell::new(None));
+        let dev_ref = device.clone();
+        let op = self.introspector.get_sink_info_by_index(
+            index,
+            move |sink_list: ListResult<&SinkInfo>| {
+                if let ListResult::Item(item) = sink_list {
+                    dev_ref.borrow_mut().replace(item.into());
+                }
+            },
+        );
         self.wait_for_result(op)?;
-        info.take()
-            .flatten()
-            .ok_or(PulseServerError::Misc("get_server_info(): failed"))
+        device
+            .take()
+            .ok_or(ServerError::Misc("get_sink(): no such sink"))
+    }
+
+    fn get_source(&mut self, index: u32) -> Result<DeviceInfo, ServerError> {
+        let device = Rc::new(RefCell::new(None));
+        let dev_ref = device.clone();
+        let op = self.introspector.get_source_info_by_index(
+            index,
+            move |source_list: ListResult<&SourceInfo>| {
+                if let ListResult::Item(item) = source_list {
+                    dev_ref.borrow_mut().replace(item.into());
+                }
+            },
+        );
+        self.wait_for_result(op)?;
+        device
+            .take()
+            .ok_or(ServerError::Misc("get_source(): no such source"))
+    }
+
+    fn get_sink_input(&mut self, index: u32) -> Result<StreamInfo, ServerError> {
+        let stream = Rc::new(RefCell::new(None));
+        let stream_ref = stream.clone();
+        let op = self.introspector.get_sink_input_info(
+            index,
+            move |input_list: ListResult<&SinkInputInfo>| {
+                if let ListResult::Item(item) = input_list {
+                    stream_ref.borrow_mut().replace(item.into());
+                }
+            },
+        );
+        self.wait_for_result(op)?;
+        stream
+            .take()
+            .ok_or(ServerError::Misc("get_sink_input(): no such stream"))
+    }
+
+    fn get_card(&mut self, index: u32) -> Result<CardInfo, ServerError> {
+        let card = Rc::new(RefCell::new(None));
+        let card_ref = card.clone();
+        let op = self.introspector.get_card_info_by_index(
+            index,
+            move |card_list: ListResult<&pulse::context::introspect::CardInfo>| {
+                if let ListResult::Item(item) = card_list {
+                    card_ref.borrow_mut().replace(item.into());
+                }
+            },
+        );
+        self.wait_for_result(op)?;
+        card.take()
+            .ok_or(ServerError::Misc("get_card(): no such card"))
+    }
+
+    fn get_default_sink(&mut self) -> Result<DeviceInfo, ServerError> {
+        let server_info = self.get_server_info();
+        match server_info {
+            Ok(info) => {
+                let name = &info.default_sink_name.unwrap_or_default();
+                let device = Rc::new(RefCell::new(Some(None)));
+                let dev_ref = device.clone();
+                let op = self.introspector.get_sink_info_by_name(
+                    name,
+                    move |sink_list: ListResult<&SinkInfo>| {
+                        if let ListResult::Item(item) = sink_list {
+                            dev_ref.borrow_mut().as_mut().unwrap().replace(item.into());
+                        }
+                    },
+                );
+                self.wait_for_result(op)?;
+                let mut result = device.borrow_mut();
+                result.take().unwrap().ok_or({
+                    ServerError::Misc("get_default_sink(): Error getting requested device")
+                })
+            }
+            Err(_) => Err(ServerError::Misc("get_default_sink() failed")),
+        }
+    }
+
+    fn get_default_source(&mut self) -> Result<DeviceInfo, ServerError> {
+        let server_info = self.get_server_info();
+        match server_info {
+            Ok(info) => {
+                let name = &info.default_source_name.unwrap_or_default();
+                let device = Rc::new(RefCell::new(Some(None)));
+                let dev_ref = device.clone();
+                let op = self.introspector.get_source_info_by_name(
+                    name,
+                    move |sink_list: ListResult<&SourceInfo>| {
+                        if let ListResult::Item(item) = sink_list {
+                            dev_ref.borrow_mut().as_mut().unwrap().replace(item.into());
+                        }
+                    },
+                );
+                self.wait_for_result(op)?;
+                let mut result = device.borrow_mut();
+                result.take().unwrap().ok_or({
+                    ServerError::Misc("get_default_source(): Error getting requested device")
+                })
+            }
+            Err(_) => Err(ServerError::Misc("get_default_source() failed")),
+        }
     }
 
-    fn set_default_sink(&mut self, sink: &str, to_move: Vec<u32>) -> bool {
+    fn set_default_sink(&mut self, sink: &str) -> bool {
+        // streams playing on the previous default sink follow it to the new one
+        let to_move = match self.get_default_sink() {
+            Ok(default_sink) => self.get_sink_inputs(default_sink.index),
+            Err(_) => return false,
+        };
         let set_default_success = Rc::new(RefCell::new(false));
         let set_default_success_ref = set_default_success.clone();
         let op = self
@@ -485,7 +929,11 @@ impl PulseServer {
         true
     }
 
-    fn set_default_source(&mut self, sink: &str, to_move: Vec<u32>) -> bool {
+    fn set_default_source(&mut self, sink: &str) -> bool {
+        let to_move = match self.get_default_source() {
+            Ok(default_source) => self.get_source_outputs(default_source.index),
+            Err(_) => return false,
+        };
         let set_default_success = Rc::new(RefCell::new(false));
         let set_default_success_ref = set_default_success.clone();
         let op = self
@@ -516,54 +964,39 @@ impl PulseServer {
         true
     }
 
-    fn get_default_sink(&mut self) -> Result<DeviceInfo, PulseServerError> {
-        let server_info = self.get_server_info();
-        match server_info {
-            Ok(info) => {
-                let name = &info.default_sink_name.unwrap_or_default();
-                let device = Rc::new(RefCell::new(Some(None)));
-                let dev_ref = device.clone();
-                let op = self.introspector.get_sink_info_by_name(
-                    name,
-                    move |sink_list: ListResult<&SinkInfo>| {
-                        if let ListResult::Item(item) = sink_list {
-                            dev_ref.borrow_mut().as_mut().unwrap().replace(item.into());
-                        }
-                    },
-                );
-                self.wait_for_result(op)?;
-                let mut result = device.borrow_mut();
-                result.take().unwrap().ok_or({
-                    PulseServerError::Misc("get_default_sink(): Error getting requested device")
-                })
-            }
-            Err(_) => Err(PulseServerError::Misc("get_default_sink() failed")),
-        }
+    fn get_cards(&mut self) -> Result<Vec<CardInfo>, ServerError> {
+        let list: Rc<RefCell<Option<Vec<CardInfo>>>> = Rc::new(RefCell::new(Some(Vec::new())));
+        let list_ref = list.clone();
+
+        let operation = self.introspector.get_card_info_list(
+            move |card_list: ListResult<&pulse::context::introspect::CardInfo>| {
+                if let ListResult::Item(item) = card_list {
+                    list_ref.borrow_mut().as_mut().unwrap().push(item.into());
+                }
+            },
+        );
+        self.wait_for_result(operation).and_then(|_| {
+            list.borrow_mut().take().ok_or(ServerError::Misc(
+                "get_cards(): failed to wait for operation",
+            ))
+        })
     }
 
-    fn get_default_source(&mut self) -> Result<DeviceInfo, PulseServerError> {
-        let server_info = self.get_server_info();
-        match server_info {
-            Ok(info) => {
-                let name = &info.default_source_name.unwrap_or_default();
-                let device = Rc::new(RefCell::new(Some(None)));
-                let dev_ref = device.clone();
-                let op = self.introspector.get_source_info_by_name(
-                    name,
-                    move |sink_list: ListResult<&SourceInfo>| {
-                        if let ListResult::Item(item) = sink_list {
-                            dev_ref.borrow_mut().as_mut().unwrap().replace(item.into());
-                        }
-                    },
-                );
-                self.wait_for_result(op)?;
-                let mut result = device.borrow_mut();
-                result.take().unwrap().ok_or({
-                    PulseServerError::Misc("get_default_source(): Error getting requested device")
-                })
-            }
-            Err(_) => Err(PulseServerError::Misc("get_default_source() failed")),
-        }
+    fn set_card_profile(&mut self, index: u32, profile: &str) {
+        let op = self
+            .introspector
+            .set_card_profile_by_index(index, profile, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_sink_port_by_name(&mut self, name: &str, port: &str) {
+        let op = self.introspector.set_sink_port_by_name(name, port, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_source_port_by_name(&mut self, name: &str, port: &str) {
+        let op = self.introspector.set_source_port_by_name(name, port, None);
+        self.wait_for_result(op).ok();
     }
 
     fn set_sink_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes) {
@@ -580,73 +1013,96 @@ impl PulseServer {
         self.wait_for_result(op).ok();
     }
 
-    fn get_source_outputs(&mut self, source: u32) -> Vec<u32> {
-        let result = Rc::new(RefCell::new(Vec::new()));
-        let result_ref = Rc::new(RefCell::new(Vec::new()));
-        let op = self.introspector.get_source_output_info_list(move |list| {
-            if let ListResult::Item(item) = list {
-                if source == item.source {
-                    result.borrow_mut().push(item.index);
-                }
-            }
-        });
+    fn set_sink_mute_by_name(&mut self, name: &str, mute: bool) {
+        let op = self.introspector.set_sink_mute_by_name(name, mute, None);
         self.wait_for_result(op).ok();
-        result_ref.replace(Vec::new())
     }
 
-    fn get_sink_inputs(&mut self, sink: u32) -> Vec<u32> {
-        let result = Rc::new(RefCell::new(Vec::new()));
-        let result_ref = Rc::new(RefCell::new(Vec::new()));
-        let op = self.introspector.get_sink_input_info_list(move |list| {
-            if let ListResult::Item(item) = list {
-                if sink == item.sink {
-                    result.borrow_mut().push(item.index);
-                }
-            }
-        });
+    fn set_source_mute_by_name(&mut self, name: &str, mute: bool) {
+        let op = self.introspector.set_source_mute_by_name(name, mute, None);
         self.wait_for_result(op).ok();
-        result_ref.replace(Vec::new())
     }
 
-    // after building an operation such as get_devices() we need to keep polling
-    // the pulse audio server to "wait" for the operation to complete
-    fn wait_for_result<G: ?Sized>(
-        &self,
-        operation: pulse::operation::Operation<G>,
-    ) -> Result<(), PulseServerError> {
-        // TODO: make this loop async. It is already in an async context, so
-        // we could make this thread sleep while waiting for the pulse server's
-        // response.
-        loop {
-            match self.mainloop.borrow_mut().iterate(false) {
-                IterateResult::Err(e) => {
-                    return Err(PulseServerError::IterateErr(IterateResult::Err(e)))
-                }
-                IterateResult::Quit(e) => {
-                    return Err(PulseServerError::IterateErr(IterateResult::Quit(e)))
-                }
-                IterateResult::Success(_) => {}
-            }
-            match operation.get_state() {
-                pulse::operation::State::Done => return Ok(()),
-                pulse::operation::State::Running => {}
-                pulse::operation::State::Cancelled => {
-                    return Err(PulseServerError::OperationErr(
-                        pulse::operation::State::Cancelled,
-                    ))
-                }
+    fn set_sink_input_volume(&mut self, index: u32, volume: &ChannelVolumes) {
+        let op = self.introspector.set_sink_input_volume(index, volume, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn set_sink_input_mute(&mut self, index: u32, mute: bool) {
+        let op = self.introspector.set_sink_input_mute(index, mute, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn move_sink_input(&mut self, index: u32, sink: &str) {
+        let op = self.introspector.move_sink_input_by_name(index, sink, None);
+        self.wait_for_result(op).ok();
+    }
+
+    fn event_fd(&self) -> RawFd {
+        self.events.as_raw_fd()
+    }
+
+    // the mainloop thread already handled the events, only the wakeups are left to read
+    fn dispatch(&mut self) -> Result<Vec<Change>, ServerError> {
+        let mut buf = [0; 64];
+        while matches!((&self.events).read(&mut buf), Ok(n) if n > 0) {}
+        let state = self.context.borrow().get_state();
+        match state {
+            pulse::context::State::Failed | pulse::context::State::Terminated => {
+                Err(ServerError::ContextErr(state))
             }
+            _ => Ok(self.changes.replace(Vec::new())),
+        }
+    }
+
+    fn lock(&mut self) {
+        if !self.locked {
+            self.mainloop.borrow_mut().lock();
+            self.locked = true;
+        }
+    }
+
+    fn unlock(&mut self) {
+        if self.locked {
+            self.mainloop.borrow_mut().unlock();
+            self.locked = false;
         }
     }
 }
 
+impl Drop for PulseServer {
+    fn drop(&mut self) {
+        self.lock();
+        self.context.borrow_mut().disconnect();
+        self.unlock();
+        self.mainloop.borrow_mut().stop();
+    }
+}
+
+fn object_kind(facility: Facility) -> Option<ObjectKind> {
+    match facility {
+        Facility::Sink => Some(ObjectKind::Sink),
+        Facility::Source => Some(ObjectKind::Source),
+        Facility::SinkInput => Some(ObjectKind::SinkInput),
+        Facility::Card => Some(ObjectKind::Card),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct DeviceInfo {
     pub name: Option<String>,
     pub description: Option<String>,
     pub volume: ChannelVolumes,
+    /// Positions of the channels in `volume`
+    pub channel_map: channelmap::Map,
     pub mute: bool,
     pub index: u32,
+    /// Index of the card the device belongs to
+    pub card: Option<u32>,
+    pub ports: Vec<PortInfo>,
+    /// Name of the active port
+    pub active_port: Option<String>,
 }
 
 impl<'a> From<&SinkInfo<'a>> for DeviceInfo {
@@ -655,8 +1111,15 @@ impl<'a> From<&SinkInfo<'a>> for DeviceInfo {
             name: info.name.clone().map(|x| x.into_owned()),
             description: info.description.clone().map(|x| x.into_owned()),
             volume: info.volume,
+            channel_map: info.channel_map,
             mute: info.mute,
             index: info.index,
+            card: info.card,
+            ports: info.ports.iter().map(PortInfo::from).collect(),
+            active_port: info
+                .active_port
+                .as_ref()
+                .and_then(|port| port.name.clone().map(|x| x.into_owned())),
         }
     }
 }
@@ -667,14 +1130,136 @@ impl<'a> From<&SourceInfo<'a>> for DeviceInfo {
             name: info.name.clone().map(|x| x.into_owned()),
             description: info.description.clone().map(|x| x.into_owned()),
             volume: info.volume,
+            channel_map: info.channel_map,
             mute: info.mute,
             index: info.index,
+            card: info.card,
+            ports: info.ports.iter().map(PortInfo::from).collect(),
+            active_port: info
+                .active_port
+                .as_ref()
+                .and_then(|port| port.name.clone().map(|x| x.into_owned())),
         }
     }
 }
 
 impl Eq for DeviceInfo {}
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortInfo {
+    pub name: String,
+    pub description: Option<String>,
+    /// False when nothing is plugged into the port
+    pub available: bool,
+}
+
+impl<'a> From<&SinkPortInfo<'a>> for PortInfo {
+    fn from(info: &SinkPortInfo<'a>) -> Self {
+        Self {
+            name: info
+                .name
+                .clone()
+                .map(|x| x.into_owned())
+                .unwrap_or_default(),
+            description: info.description.clone().map(|x| x.into_owned()),
+            available: info.available != PortAvailable::No,
+        }
+    }
+}
+
+impl<'a> From<&SourcePortInfo<'a>> for PortInfo {
+    fn from(info: &SourcePortInfo<'a>) -> Self {
+        Self {
+            name: info
+                .name
+                .clone()
+                .map(|x| x.into_owned())
+                .unwrap_or_default(),
+            description: info.description.clone().map(|x| x.into_owned()),
+            available: info.available != PortAvailable::No,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub available: bool,
+}
+
+impl<'a> From<&CardProfileInfo<'a>> for ProfileInfo {
+    fn from(info: &CardProfileInfo<'a>) -> Self {
+        Self {
+            name: info
+                .name
+                .clone()
+                .map(|x| x.into_owned())
+                .unwrap_or_default(),
+            description: info.description.clone().map(|x| x.into_owned()),
+            available: info.available,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardInfo {
+    pub index: u32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub profiles: Vec<ProfileInfo>,
+    /// Name of the active profile
+    pub active_profile: Option<String>,
+}
+
+impl<'a> From<&pulse::context::introspect::CardInfo<'a>> for CardInfo {
+    fn from(info: &pulse::context::introspect::CardInfo<'a>) -> Self {
+        Self {
+            index: info.index,
+            name: info.name.clone().map(|x| x.into_owned()),
+            description: info
+                .proplist
+                .get_str(pulse::proplist::properties::DEVICE_DESCRIPTION),
+            profiles: info.profiles.iter().map(ProfileInfo::from).collect(),
+            active_profile: info
+                .active_profile
+                .as_ref()
+                .and_then(|profile| profile.name.clone().map(|x| x.into_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub index: u32,
+    pub name: Option<String>,
+    pub icon_name: Option<String>,
+    pub volume: ChannelVolumes,
+    pub mute: bool,
+    /// Index of the sink the stream is playing on
+    pub sink: u32,
+}
+
+impl<'a> From<&SinkInputInfo<'a>> for StreamInfo {
+    fn from(info: &SinkInputInfo<'a>) -> Self {
+        Self {
+            index: info.index,
+            name: info
+                .proplist
+                .get_str(pulse::proplist::properties::APPLICATION_NAME)
+                .or_else(|| info.name.clone().map(|x| x.into_owned())),
+            icon_name: info
+                .proplist
+                .get_str(pulse::proplist::properties::APPLICATION_ICON_NAME),
+            volume: info.volume,
+            mute: info.mute,
+            sink: info.sink,
+        }
+    }
+}
+
+impl Eq for StreamInfo {}
+
 #[derive(Debug)]
 pub struct ServerInfo {
     /// User name of the daemon process.
@@ -0,0 +1,130 @@
+#[cfg(feature = "pipewire")]
+use crate::pipewire_server::PipeWireServer;
+use crate::pulse::{CardInfo, DeviceInfo, PulseServer, ServerInfo, StreamInfo};
+
+use libpulse_binding::{context, error::PAErr, operation, volume::ChannelVolumes};
+use std::os::unix::io::RawFd;
+
+/// Sound server backend driven by the pulse thread
+///
+/// Volumes are exchanged as PulseAudio `ChannelVolumes` whichever server is used,
+/// so the applet state is the same for every backend.
+pub trait AudioServer {
+    fn get_server_info(&mut self) -> Result<ServerInfo, ServerError>;
+
+    // Get a list of output devices
+    fn get_sinks(&mut self) -> Result<Vec<DeviceInfo>, ServerError>;
+
+    // Get a list of input devices
+    fn get_sources(&mut self) -> Result<Vec<DeviceInfo>, ServerError>;
+
+    // Get a list of playback streams
+    fn get_sink_input_list(&mut self) -> Result<Vec<StreamInfo>, ServerError>;
+
+    // Get a list of sound cards and their profiles
+    fn get_cards(&mut self) -> Result<Vec<CardInfo>, ServerError>;
+
+    fn get_sink(&mut self, index: u32) -> Result<DeviceInfo, ServerError>;
+
+    fn get_source(&mut self, index: u32) -> Result<DeviceInfo, ServerError>;
+
+    fn get_sink_input(&mut self, index: u32) -> Result<StreamInfo, ServerError>;
+
+    fn get_card(&mut self, index: u32) -> Result<CardInfo, ServerError>;
+
+    fn get_default_sink(&mut self) -> Result<DeviceInfo, ServerError>;
+
+    fn get_default_source(&mut self) -> Result<DeviceInfo, ServerError>;
+
+    // Make the sink the default, moving the streams playing on the previous default
+    fn set_default_sink(&mut self, sink: &str) -> bool;
+
+    // Make the source the default, moving the streams recording from the previous default
+    fn set_default_source(&mut self, source: &str) -> bool;
+
+    fn set_card_profile(&mut self, index: u32, profile: &str);
+
+    fn set_sink_port_by_name(&mut self, name: &str, port: &str);
+
+    fn set_source_port_by_name(&mut self, name: &str, port: &str);
+
+    fn set_sink_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes);
+
+    fn set_source_volume_by_name(&mut self, name: &str, volume: &ChannelVolumes);
+
+    fn set_sink_mute_by_name(&mut self, name: &str, mute: bool);
+
+    fn set_source_mute_by_name(&mut self, name: &str, mute: bool);
+
+    fn set_sink_input_volume(&mut self, index: u32, volume: &ChannelVolumes);
+
+    fn set_sink_input_mute(&mut self, index: u32, mute: bool);
+
+    fn move_sink_input(&mut self, index: u32, sink: &str);
+
+    // Readable when the server has events, so the pulse thread only wakes up for them
+    fn event_fd(&self) -> RawFd;
+
+    // Dispatch pending server events and take the objects that changed
+    fn dispatch(&mut self) -> Result<Vec<Change>, ServerError>;
+
+    // Keep the server's own event thread, if it has one, from running while requests are made
+    fn lock(&mut self) {}
+
+    fn unlock(&mut self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Sink,
+    Source,
+    SinkInput,
+    Card,
+}
+
+// A change reported by the server, only the changed object is fetched again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    // The object was added or changed
+    Changed(ObjectKind, u32),
+    Removed(ObjectKind, u32),
+    // The default devices changed
+    Server,
+}
+
+// Add a change unless it is already pending
+pub fn push_change(changes: &mut Vec<Change>, change: Change) {
+    if !changes.contains(&change) {
+        changes.push(change);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ServerError {
+    ContextErr(context::State),
+    OperationErr(operation::State),
+    PAErr(PAErr),
+    #[cfg(feature = "pipewire")]
+    PipeWire(String),
+    Connect,
+    Misc(&'static str),
+}
+
+// Connect to PipeWire if it is running, falling back to PulseAudio otherwise
+pub fn connect() -> Result<Box<dyn AudioServer>, ServerError> {
+    #[cfg(feature = "pipewire")]
+    match PipeWireServer::connect() {
+        Ok(server) => {
+            log::info!("Using the PipeWire backend");
+            return Ok(Box::new(server));
+        }
+        Err(err) => log::info!("PipeWire unavailable: {:?}", err),
+    }
+
+    let mut server = PulseServer::connect().and_then(|server| server.init())?;
+    if let Err(err) = server.subscribe() {
+        log::warn!("Failed to subscribe to server events: {:?}", err);
+    }
+    log::info!("Using the PulseAudio backend");
+    Ok(Box::new(server))
+}
@@ -5,9 +5,33 @@ balanced = Balanced
 balanced-desc = Standard performance and battery usage.
 performance = High Performance
 performance-desc = High performance and power usage.
-max-charge = Increase the lifespan of your battery by setting a maximum charge value of 80%
+max-charge = Increase the lifespan of your battery by setting a maximum charge value of {$percent}%
+max-charge-generic = Increase the lifespan of your battery by setting a maximum charge value
+max-charge-unsupported = Not available, power-profiles-daemon can't limit the charge.
+charge-thresholds = Charge Thresholds
 seconds = s
 minutes = m
 hours = h
 until-empty = until empty
 power-settings = Power and Battery Settings...
+unknown-device = Unknown Device
+battery-details = Battery Details
+health = Health
+charge-cycles = Charge Cycles
+power-draw = Power Draw
+charge-history = Charge, last 24 hours
+rate-history = Power draw, last 24 hours
+battery-low = Battery low
+battery-critical = Battery critically low
+battery-critical-desc = Plug in the power adapter, the computer will {$action} soon.
+critical-action-countdown = The computer will {$action} in {$seconds}s
+power-off = power off
+hibernate = hibernate
+hybrid-sleep = hybrid sleep
+suspend = suspend
+charging = Charging
+until-full = until full
+fully-charged = Fully charged
+not-charging = Plugged in, not charging
+performance-degraded = Performance is limited ({$reason}).
+profile-hold = {$application} holds the {$profile} profile: {$reason}
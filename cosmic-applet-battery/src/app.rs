@@ -1,12 +1,19 @@
 use crate::backlight::{
-    screen_backlight_subscription, ScreenBacklightRequest, ScreenBacklightUpdate,
+    screen_backlight_subscription, ScreenBacklightRequest, ScreenBacklightUpdate, ScreenBrightness,
 };
-use crate::config;
+use crate::config::{self, BatteryConfig};
 use crate::fl;
+use crate::idle::{idle_subscription, IdleUpdate};
+use crate::notifications::notify;
 use crate::power_daemon::{
-    power_profile_subscription, Power, PowerProfileRequest, PowerProfileUpdate,
+    power_profile_subscription, ChargeProfile, Power, PowerProfileRequest, PowerProfileUpdate,
+    ProfileStatus,
 };
-use crate::upower_device::{device_subscription, DeviceDbusEvent};
+use crate::upower_device::{
+    battery_details, device_subscription, BatteryDetails, BatteryState, DeviceDbusEvent,
+    WarningLevel,
+};
+use crate::upower_devices::{devices_subscription, DevicesUpdate, PowerDevice};
 use crate::upower_kbdbacklight::{
     kbd_backlight_subscription, KeyboardBacklightRequest, KeyboardBacklightUpdate,
 };
@@ -15,7 +22,8 @@ use cosmic::iced::alignment::Horizontal;
 use cosmic::iced::wayland::popup::{destroy_popup, get_popup};
 use cosmic::iced::wayland::SurfaceIdWrapper;
 use cosmic::iced::{
-    widget::{column, container, row, slider, text},
+    time,
+    widget::{column, container, progress_bar, row, slider, text, vertical_space},
     window, Alignment, Application, Command, Length, Subscription,
 };
 use cosmic::iced_native::layout::Limits;
@@ -24,8 +32,9 @@ use cosmic::iced_style::Color;
 use cosmic::theme::Svg;
 use cosmic::widget::{button, divider, icon, toggler};
 use cosmic::{Element, Theme};
+use cosmic_applet_osd::{self as osd, Osd};
 use log::error;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::UnboundedSender;
 
 // XXX improve
@@ -35,7 +44,13 @@ fn format_duration(duration: Duration) -> String {
     if secs > 60 {
         let min = secs / 60;
         if min > 60 {
-            format!("{}:{:02}", min / 60, min % 60)
+            format!(
+                "{}{} {}{}",
+                min / 60,
+                fl!("hours"),
+                min % 60,
+                fl!("minutes")
+            )
         } else {
             format!("{}{}", min, fl!("minutes"))
         }
@@ -54,17 +69,58 @@ struct CosmicBatteryApplet {
     icon_name: String,
     theme: Theme,
     charging_limit: bool,
+    charge_profiles: Vec<ChargeProfile>,
+    charge_thresholds: Option<(u8, u8)>,
+    // limiting profile applied when the max charge toggle is turned on
+    charge_limit_profile: Option<String>,
+    show_charge_profiles: bool,
+    // the power daemon has no charge thresholds, like power-profiles-daemon
+    charge_thresholds_unsupported: bool,
     battery_percent: f64,
     time_remaining: Duration,
+    time_to_full: Duration,
+    energy_rate: f64,
+    battery_state: BatteryState,
+    on_battery: bool,
+    // whether `on_battery` was received, so the automatic profile only follows changes
+    on_battery_known: bool,
+    switched_to_power_saver: bool,
+    warning_level: WarningLevel,
+    critical_action: String,
+    config: BatteryConfig,
+    // lowest threshold notified since the battery started discharging
+    warned_at: Option<u8>,
+    warned_critical: bool,
+    notification_id: u32,
+    critical_action_deadline: Option<Instant>,
+    devices: Vec<PowerDevice>,
+    show_details: bool,
+    details: Option<BatteryDetails>,
     kbd_brightness: f64,
-    screen_brightness: f64,
+    // number of steps of the keyboard backlight
+    kbd_max: i32,
+    // brightness restored once the user is active again
+    kbd_before_idle: Option<f64>,
+    screens: Vec<ScreenBrightness>,
     popup: Option<window::Id>,
     id_ctr: u32,
     screen_sender: Option<UnboundedSender<ScreenBacklightRequest>>,
     kbd_sender: Option<UnboundedSender<KeyboardBacklightRequest>>,
     applet_helper: CosmicAppletHelper,
-    power_profile: Power,
+    profile_status: ProfileStatus,
     power_profile_sender: Option<UnboundedSender<PowerProfileRequest>>,
+    osd: Osd,
+    osd_kind: OsdKind,
+    // index of the screen shown by the OSD
+    osd_screen: usize,
+}
+
+// The brightness shown by the OSD
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OsdKind {
+    #[default]
+    Screen,
+    Keyboard,
 }
 
 #[derive(Debug, Clone)]
@@ -74,22 +130,535 @@ enum Message {
         icon_name: String,
         percent: f64,
         time_to_empty: i64,
+        time_to_full: i64,
+        energy_rate: f64,
+        state: BatteryState,
+        warning_level: WarningLevel,
+        on_battery: bool,
+        critical_action: String,
     },
+    Notified(Option<u32>),
+    Tick,
+    Devices(Vec<PowerDevice>),
+    ToggleDetails,
+    Details(Option<BatteryDetails>),
     SetKbdBrightness(i32),
-    SetScreenBrightness(i32),
+    SetScreenBrightness(usize, i32),
     SetChargingLimit(bool),
+    SetChargeProfile(String),
+    ToggleChargeProfiles,
+    ChargeThresholds(Vec<ChargeProfile>, (u8, u8)),
+    ChargeThresholdsUnsupported,
     UpdateKbdBrightness(f64),
-    UpdateScreenBrightness(f64),
+    KbdBrightnessChanged(f64),
+    HideOsd(u32),
+    UpdateScreenBrightness(Vec<ScreenBrightness>),
+    ScreenBrightnessChanged(usize, Vec<ScreenBrightness>),
     OpenBatterySettings,
-    InitKbdBacklight(UnboundedSender<KeyboardBacklightRequest>, f64),
-    InitScreenBacklight(UnboundedSender<ScreenBacklightRequest>, f64),
+    InitKbdBacklight(UnboundedSender<KeyboardBacklightRequest>, f64, i32),
+    KbdIdle(IdleUpdate),
+    InitScreenBacklight(
+        UnboundedSender<ScreenBacklightRequest>,
+        Vec<ScreenBrightness>,
+    ),
     Errored(String),
     Ignore,
-    InitProfile(UnboundedSender<PowerProfileRequest>, Power),
-    Profile(Power),
+    InitProfile(UnboundedSender<PowerProfileRequest>, ProfileStatus),
+    Profile(ProfileStatus),
     SelectProfile(Power),
 }
 
+impl CosmicBatteryApplet {
+    // The popup already shows the brightness when it is open
+    fn show_osd(&mut self, kind: OsdKind) -> Command<Message> {
+        if self.popup.is_some() {
+            return Command::none();
+        }
+        self.osd_kind = kind;
+        self.osd.show(&mut self.id_ctr, Message::HideOsd)
+    }
+
+    fn battery_description(&self) -> String {
+        match self.battery_state {
+            BatteryState::FullyCharged => fl!("fully-charged"),
+            BatteryState::Charging if self.time_to_full.is_zero() => {
+                format!("{} ({:.0}%)", fl!("charging"), self.battery_percent)
+            }
+            BatteryState::Charging => format!(
+                "{} — {} {} ({:.0}%, {:.1} W)",
+                fl!("charging"),
+                format_duration(self.time_to_full),
+                fl!("until-full"),
+                self.battery_percent,
+                self.energy_rate
+            ),
+            _ if !self.on_battery => {
+                format!("{} ({:.0}%)", fl!("not-charging"), self.battery_percent)
+            }
+            _ if self.time_remaining.is_zero() => format!("{:.0}%", self.battery_percent),
+            _ => format!(
+                "{} {} ({:.0}%)",
+                format_duration(self.time_remaining),
+                fl!("until-empty"),
+                self.battery_percent
+            ),
+        }
+    }
+
+    // Switch to the configured profiles when the power adapter is (un)plugged,
+    // and once to the battery profile when below the configured percentage
+    fn automatic_profile(&mut self, was_on_battery: Option<bool>) {
+        let mut profile = match was_on_battery {
+            Some(was_on_battery) if was_on_battery != self.on_battery => {
+                if self.on_battery {
+                    self.config.on_battery_profile
+                } else {
+                    self.switched_to_power_saver = false;
+                    self.config.on_ac_profile
+                }
+            }
+            _ => None,
+        };
+        let low = self
+            .config
+            .power_saver_below
+            .map_or(false, |p| self.battery_percent < f64::from(p));
+        let low_battery_switch = self.on_battery && low && !self.switched_to_power_saver;
+        if low_battery_switch {
+            profile = Some(Power::Battery);
+        }
+        // the power daemon may not be connected yet, the low battery switch is
+        // made once it is
+        if let (Some(profile), Some(tx)) = (profile, self.power_profile_sender.as_ref()) {
+            if profile != self.profile_status.profile {
+                let _ = tx.send(PowerProfileRequest::Set(profile));
+            }
+            if low_battery_switch {
+                self.switched_to_power_saver = true;
+            }
+        }
+    }
+
+    // Only used for the countdown shown, UPower takes the action on its own
+    fn critical_action_delay(&self) -> Duration {
+        Duration::from_secs(self.config.critical_action_delay.into())
+    }
+
+    // Notify once for each threshold crossed while discharging, and when UPower
+    // is about to take the critical action
+    fn low_battery_warnings(&mut self) -> Command<Message> {
+        if !self.on_battery {
+            self.warned_at = None;
+            self.warned_critical = false;
+            self.critical_action_deadline = None;
+            return Command::none();
+        }
+        if self.warning_level >= WarningLevel::Critical && !self.warned_critical {
+            self.warned_critical = true;
+            if self.warning_level == WarningLevel::Action {
+                self.critical_action_deadline = Some(Instant::now() + self.critical_action_delay());
+            }
+            return self.notify(
+                fl!("battery-critical"),
+                fl!(
+                    "battery-critical-desc",
+                    action = self.critical_action_name()
+                ),
+                true,
+            );
+        }
+        if self.warning_level == WarningLevel::Action && self.critical_action_deadline.is_none() {
+            self.critical_action_deadline = Some(Instant::now() + self.critical_action_delay());
+        }
+        let threshold = self
+            .config
+            .warning_thresholds
+            .iter()
+            .copied()
+            .filter(|t| self.battery_percent <= f64::from(*t))
+            .min();
+        match threshold {
+            Some(t) if self.warned_at.map_or(true, |warned_at| t < warned_at) => {
+                self.warned_at = Some(t);
+                self.notify(
+                    fl!("battery-low"),
+                    format!(
+                        "{:.0}% ({} {})",
+                        self.battery_percent,
+                        format_duration(self.time_remaining),
+                        fl!("until-empty")
+                    ),
+                    false,
+                )
+            }
+            _ => Command::none(),
+        }
+    }
+
+    fn notify(&self, summary: String, body: String, critical: bool) -> Command<Message> {
+        let replaces_id = self.notification_id;
+        let icon_name = self.icon_name.clone();
+        Command::perform(
+            async move {
+                notify(replaces_id, &icon_name, &summary, &body, critical)
+                    .await
+                    .map_err(|e| error!("Failed to send notification: {}", e))
+                    .ok()
+            },
+            Message::Notified,
+        )
+    }
+
+    fn critical_action_name(&self) -> String {
+        match self.critical_action.as_str() {
+            "Hibernate" => fl!("hibernate"),
+            "HybridSleep" => fl!("hybrid-sleep"),
+            "Suspend" => fl!("suspend"),
+            _ => fl!("power-off"),
+        }
+    }
+
+    fn critical_action_countdown(&self) -> Option<String> {
+        let remaining = self
+            .critical_action_deadline?
+            .saturating_duration_since(Instant::now());
+        Some(fl!(
+            "critical-action-countdown",
+            action = self.critical_action_name(),
+            seconds = remaining.as_secs()
+        ))
+    }
+
+    // The chosen limiting profile, or the first one which doesn't charge to 100%
+    fn charge_limit_profile(&self) -> Option<&ChargeProfile> {
+        self.charge_limit_profile
+            .as_ref()
+            .and_then(|id| self.charge_profiles.iter().find(|p| &p.id == id))
+            .or_else(|| self.charge_profiles.iter().find(|p| p.end < 100))
+    }
+
+    // The profile charging the furthest, used when the limit is turned off
+    fn full_charge_profile(&self) -> Option<&ChargeProfile> {
+        self.charge_profiles.iter().max_by_key(|p| p.end)
+    }
+
+    fn set_charge_thresholds(&self, thresholds: (u8, u8)) {
+        if let Some(tx) = self.power_profile_sender.as_ref() {
+            let _ = tx.send(PowerProfileRequest::SetChargeThresholds(thresholds));
+        }
+    }
+
+    // A brightness slider for each screen, labeled when there are several
+    fn screen_sliders(&self) -> Element<Message> {
+        let labeled = self.screens.len() > 1;
+        let sliders = self.screens.iter().enumerate().map(|(index, screen)| {
+            let brightness = row![
+                icon("display-brightness-symbolic", 24)
+                    .style(Svg::Symbolic)
+                    .width(Length::Units(24))
+                    .height(Length::Units(24)),
+                slider(
+                    1..=100,
+                    (screen.brightness * 100.0) as i32,
+                    move |brightness| Message::SetScreenBrightness(index, brightness)
+                ),
+                text(format!("{:.0}%", screen.brightness * 100.0))
+                    .width(Length::Units(40))
+                    .horizontal_alignment(Horizontal::Right)
+            ]
+            .padding([0, 24])
+            .spacing(12);
+            if labeled {
+                column![
+                    container(text(&screen.name).size(12))
+                        .padding([0, 24])
+                        .width(Length::Fill),
+                    brightness
+                ]
+                .spacing(4)
+                .into()
+            } else {
+                brightness.into()
+            }
+        });
+        column(sliders.collect()).spacing(8).into()
+    }
+
+    // The profiles supported by the backend, with what limits them
+    fn profiles(&self) -> Element<Message> {
+        let status = &self.profile_status;
+        let mut profiles = column![].spacing(8);
+        for profile in &status.profiles {
+            let (name, mut description) = profile_text(*profile);
+            if let (Power::Performance, Some(reason)) = (profile, &status.degraded) {
+                description = fl!("performance-degraded", reason = reason.as_str());
+            }
+            profiles = profiles.push(
+                button(APPLET_BUTTON_THEME)
+                    .custom(vec![row![
+                        column![text(name).size(14), text(description).size(12)]
+                            .width(Length::Fill),
+                        icon("emblem-ok-symbolic", 12).size(12).style(
+                            if status.profile == *profile {
+                                Svg::SymbolicActive
+                            } else {
+                                Svg::Default
+                            }
+                        ),
+                    ]
+                    .align_items(Alignment::Center)
+                    .into()])
+                    .padding([8, 24])
+                    .on_press(Message::SelectProfile(*profile))
+                    .width(Length::Fill),
+            );
+        }
+        for hold in &status.holds {
+            profiles = profiles.push(
+                container(
+                    text(fl!(
+                        "profile-hold",
+                        application = hold.application_id.as_str(),
+                        profile = profile_text(hold.profile).0,
+                        reason = hold.reason.as_str()
+                    ))
+                    .size(12),
+                )
+                .padding([0, 24])
+                .width(Length::Fill),
+            );
+        }
+        profiles.into()
+    }
+
+    // Peripherals and UPSes with their own battery
+    fn devices(&self) -> Element<Message> {
+        if self.devices.is_empty() {
+            return column![].into();
+        }
+        let devices = self.devices.iter().map(|device| {
+            row![
+                icon(device.icon_name(), 24)
+                    .style(Svg::Symbolic)
+                    .width(Length::Units(24))
+                    .height(Length::Units(24)),
+                text(if device.model.is_empty() {
+                    fl!("unknown-device")
+                } else {
+                    device.model.clone()
+                })
+                .size(14)
+                .width(Length::Fill),
+                text(format!("{:.0}%", device.percent))
+                    .size(14)
+                    .width(Length::Units(40))
+                    .horizontal_alignment(Horizontal::Right)
+            ]
+            .padding([0, 24])
+            .spacing(12)
+            .align_items(Alignment::Center)
+            .into()
+        });
+        column![
+            column(devices.collect()).spacing(8),
+            container(divider::horizontal::light())
+                .width(Length::Fill)
+                .padding([0, 12]),
+        ]
+        .spacing(8)
+        .into()
+    }
+
+    // Health, power draw and history of the battery, read when expanded
+    fn details(&self) -> Element<Message> {
+        let mut content = column![button(APPLET_BUTTON_THEME)
+            .custom(vec![
+                text(fl!("battery-details")).width(Length::Fill).into(),
+                icon(
+                    if self.show_details {
+                        "go-up-symbolic"
+                    } else {
+                        "go-down-symbolic"
+                    },
+                    12
+                )
+                .size(12)
+                .style(Svg::Symbolic)
+                .into(),
+            ])
+            .padding([8, 24])
+            .width(Length::Fill)
+            .on_press(Message::ToggleDetails)]
+        .spacing(8);
+        let details = match self.details.as_ref().filter(|_| self.show_details) {
+            Some(details) => details,
+            None => return content.into(),
+        };
+        let detail = |name: String, value: String| {
+            row![
+                text(name).size(14).width(Length::Fill),
+                text(value).size(14)
+            ]
+            .padding([0, 24])
+        };
+        if let Some(health) = details.health {
+            content = content.push(detail(fl!("health"), format!("{:.0}%", health)));
+        }
+        if let Some(cycles) = details.charge_cycles {
+            content = content.push(detail(fl!("charge-cycles"), cycles.to_string()));
+        }
+        content = content.push(detail(
+            fl!("power-draw"),
+            format!("{:.1} W", details.energy_rate),
+        ));
+        if !details.charge_history.is_empty() {
+            content = content
+                .push(
+                    container(text(fl!("charge-history")).size(12))
+                        .padding([0, 24])
+                        .width(Length::Fill),
+                )
+                .push(history_chart(&details.charge_history, 100.0));
+        }
+        let max_rate = details.rate_history.iter().copied().fold(0.0, f64::max);
+        if max_rate > 0.0 {
+            content = content
+                .push(
+                    container(text(fl!("rate-history")).size(12))
+                        .padding([0, 24])
+                        .width(Length::Fill),
+                )
+                .push(history_chart(&details.rate_history, max_rate));
+        }
+        content.into()
+    }
+
+    // Max charge toggle and the picker of the daemon's charge profiles
+    fn charge_limit(&self) -> Element<Message> {
+        let limit = match self.charge_limit_profile() {
+            Some(profile) => profile,
+            None if self.charge_thresholds_unsupported => return self.charge_limit_unsupported(),
+            None => return column![].into(),
+        };
+        let mut content = column![
+            container(toggler(
+                fl!("max-charge", percent = limit.end),
+                self.charging_limit,
+                Message::SetChargingLimit
+            ))
+            .padding([0, 24])
+            .width(Length::Fill),
+            button(APPLET_BUTTON_THEME)
+                .custom(vec![
+                    text(fl!("charge-thresholds")).width(Length::Fill).into(),
+                    icon(
+                        if self.show_charge_profiles {
+                            "go-up-symbolic"
+                        } else {
+                            "go-down-symbolic"
+                        },
+                        12
+                    )
+                    .size(12)
+                    .style(Svg::Symbolic)
+                    .into(),
+                ])
+                .padding([8, 24])
+                .width(Length::Fill)
+                .on_press(Message::ToggleChargeProfiles),
+        ]
+        .spacing(8);
+        if self.show_charge_profiles {
+            for profile in &self.charge_profiles {
+                content = content.push(
+                    button(APPLET_BUTTON_THEME)
+                        .custom(vec![row![
+                            column![
+                                text(&profile.title).size(14),
+                                text(format!(
+                                    "{} ({}–{}%)",
+                                    profile.description, profile.start, profile.end
+                                ))
+                                .size(12)
+                            ]
+                            .width(Length::Fill),
+                            icon("emblem-ok-symbolic", 12).size(12).style(
+                                if self.charge_thresholds == Some(profile.thresholds()) {
+                                    Svg::SymbolicActive
+                                } else {
+                                    Svg::Default
+                                }
+                            ),
+                        ]
+                        .align_items(Alignment::Center)
+                        .into()])
+                        .padding([8, 48])
+                        .on_press(Message::SetChargeProfile(profile.id.clone()))
+                        .width(Length::Fill),
+                );
+            }
+        }
+        column![
+            content,
+            container(divider::horizontal::light())
+                .width(Length::Fill)
+                .padding([0, 12]),
+        ]
+        .spacing(8)
+        .into()
+    }
+
+    // The max charge toggle, turned off, explaining why it can't be used
+    fn charge_limit_unsupported(&self) -> Element<Message> {
+        column![
+            container(
+                column![
+                    toggler(fl!("max-charge-generic"), false, |_| Message::Ignore),
+                    text(fl!("max-charge-unsupported")).size(12),
+                ]
+                .spacing(4)
+            )
+            .padding([0, 24])
+            .width(Length::Fill),
+            container(divider::horizontal::light())
+                .width(Length::Fill)
+                .padding([0, 12]),
+        ]
+        .spacing(8)
+        .into()
+    }
+}
+
+// Name and description of a power profile
+fn profile_text(profile: Power) -> (String, String) {
+    match profile {
+        Power::Battery => (fl!("battery"), fl!("battery-desc")),
+        Power::Balanced => (fl!("balanced"), fl!("balanced-desc")),
+        Power::Performance => (fl!("performance"), fl!("performance-desc")),
+    }
+}
+
+// Bar chart of history samples, each bar is a full progress bar sized to the sample
+fn history_chart(samples: &[f64], max: f64) -> Element<'static, Message> {
+    let bars = samples.iter().map(|sample| {
+        let portion = ((sample / max).clamp(0.0, 1.0) * 100.0).round() as u16;
+        column![
+            vertical_space(Length::FillPortion(100 - portion.min(99))),
+            progress_bar(0.0..=1.0, 1.0)
+                .width(Length::Fill)
+                .height(Length::FillPortion(portion.max(1))),
+        ]
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+    });
+    row(bars.collect())
+        .spacing(1)
+        .height(Length::Units(48))
+        .padding([0, 24])
+        .into()
+}
+
 impl Application for CosmicBatteryApplet {
     type Message = Message;
     type Theme = Theme;
@@ -100,6 +669,7 @@ impl Application for CosmicBatteryApplet {
         (
             CosmicBatteryApplet {
                 icon_name: "battery-symbolic".to_string(),
+                config: BatteryConfig::load().unwrap_or_default(),
                 ..Default::default()
             },
             Command::none(),
@@ -112,20 +682,58 @@ impl Application for CosmicBatteryApplet {
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::SetKbdBrightness(brightness) => {
-                self.kbd_brightness = (brightness as f64 / 100.0).clamp(0., 1.);
+            Message::SetKbdBrightness(step) => {
+                self.kbd_brightness = (step as f64 / self.kbd_max.max(1) as f64).clamp(0., 1.);
+                self.kbd_before_idle = None;
                 if let Some(tx) = &self.kbd_sender {
                     let _ = tx.send(KeyboardBacklightRequest::Set(self.kbd_brightness));
                 }
             }
-            Message::SetScreenBrightness(brightness) => {
-                self.screen_brightness = (brightness as f64 / 100.0).clamp(0.01, 1.0);
-                if let Some(tx) = &self.screen_sender {
-                    let _ = tx.send(ScreenBacklightRequest::Set(self.screen_brightness));
+            Message::SetScreenBrightness(index, brightness) => {
+                if let Some(screen) = self.screens.get_mut(index) {
+                    screen.brightness = (brightness as f64 / 100.0).clamp(0.01, 1.0);
+                    if let Some(tx) = &self.screen_sender {
+                        let _ = tx.send(ScreenBacklightRequest::Set(index, screen.brightness));
+                    }
                 }
             }
             Message::SetChargingLimit(enable_charging_limit) => {
-                self.charging_limit = enable_charging_limit;
+                let profile = if enable_charging_limit {
+                    self.charge_limit_profile()
+                } else {
+                    self.full_charge_profile()
+                };
+                if let Some(thresholds) = profile.map(ChargeProfile::thresholds) {
+                    self.charging_limit = enable_charging_limit;
+                    self.set_charge_thresholds(thresholds);
+                }
+            }
+            Message::SetChargeProfile(id) => {
+                if let Some(profile) = self.charge_profiles.iter().find(|p| p.id == id) {
+                    let thresholds = profile.thresholds();
+                    if profile.end < 100 {
+                        self.charge_limit_profile = Some(id);
+                    }
+                    self.set_charge_thresholds(thresholds);
+                }
+            }
+            Message::ToggleChargeProfiles => {
+                self.show_charge_profiles = !self.show_charge_profiles;
+            }
+            Message::ChargeThresholds(profiles, thresholds) => {
+                self.charge_profiles = profiles;
+                self.charge_thresholds = Some(thresholds);
+                self.charging_limit = thresholds.1 < 100;
+                if let Some(profile) = self
+                    .charge_profiles
+                    .iter()
+                    .find(|p| p.thresholds() == thresholds && p.end < 100)
+                {
+                    self.charge_limit_profile = Some(profile.id.clone());
+                }
+            }
+            Message::ChargeThresholdsUnsupported => {
+                self.charge_thresholds_unsupported = true;
             }
             Message::OpenBatterySettings => {
                 // TODO Ashley
@@ -141,7 +749,7 @@ impl Application for CosmicBatteryApplet {
                         let _ = tx.send(KeyboardBacklightRequest::Get);
                     }
                     if let Some(tx) = &self.screen_sender {
-                        let _ = tx.send(ScreenBacklightRequest::Get);
+                        let _ = tx.send(ScreenBacklightRequest::Refresh);
                     }
 
                     self.id_ctr += 1;
@@ -162,6 +770,7 @@ impl Application for CosmicBatteryApplet {
                         .max_height(1080);
                     if let Some(tx) = self.power_profile_sender.as_ref() {
                         let _ = tx.send(PowerProfileRequest::Get);
+                        let _ = tx.send(PowerProfileRequest::GetChargeThresholds);
                     }
                     return get_popup(popup_settings);
                 }
@@ -170,34 +779,109 @@ impl Application for CosmicBatteryApplet {
                 icon_name,
                 percent,
                 time_to_empty,
+                time_to_full,
+                energy_rate,
+                state,
+                warning_level,
+                on_battery,
+                critical_action,
             } => {
                 self.icon_name = icon_name;
                 self.battery_percent = percent;
                 self.time_remaining = Duration::from_secs(time_to_empty as u64);
+                self.time_to_full = Duration::from_secs(time_to_full as u64);
+                self.energy_rate = energy_rate;
+                self.battery_state = state;
+                self.warning_level = warning_level;
+                let was_on_battery = self.on_battery_known.then_some(self.on_battery);
+                self.on_battery = on_battery;
+                self.on_battery_known = true;
+                self.automatic_profile(was_on_battery);
+                self.critical_action = critical_action;
+                return self.low_battery_warnings();
+            }
+            Message::Notified(id) => {
+                if let Some(id) = id {
+                    self.notification_id = id;
+                }
+            }
+            Message::Tick => {}
+            Message::Devices(devices) => {
+                self.devices = devices;
+            }
+            Message::ToggleDetails => {
+                self.show_details = !self.show_details;
+                if self.show_details {
+                    return Command::perform(
+                        async {
+                            battery_details()
+                                .await
+                                .map_err(|e| error!("Failed to get battery details: {}", e))
+                                .ok()
+                        },
+                        Message::Details,
+                    );
+                }
+            }
+            Message::Details(details) => {
+                self.details = details;
             }
             Message::UpdateKbdBrightness(b) => {
                 self.kbd_brightness = b;
             }
+            Message::KbdBrightnessChanged(b) => {
+                self.kbd_brightness = b;
+                return self.show_osd(OsdKind::Keyboard);
+            }
+            Message::HideOsd(generation) => return self.osd.hide(generation),
             Message::Ignore => {}
-            Message::InitKbdBacklight(tx, brightness) => {
+            Message::InitKbdBacklight(tx, brightness, max) => {
                 let _ = tx.send(KeyboardBacklightRequest::Get);
                 self.kbd_sender = Some(tx);
                 self.kbd_brightness = brightness;
+                self.kbd_max = max;
+            }
+            Message::KbdIdle(IdleUpdate::Idle) => {
+                if self.kbd_brightness > 0. {
+                    if let Some(tx) = &self.kbd_sender {
+                        self.kbd_before_idle = Some(self.kbd_brightness);
+                        self.kbd_brightness = 0.;
+                        let _ = tx.send(KeyboardBacklightRequest::Set(0.));
+                    }
+                }
             }
-            Message::InitScreenBacklight(tx, brightness) => {
+            Message::KbdIdle(IdleUpdate::Resumed) => {
+                // keep the level if it was changed while idle, e.g. with the hardware keys
+                if let Some(brightness) = self.kbd_before_idle.take() {
+                    if self.kbd_brightness == 0. {
+                        self.kbd_brightness = brightness;
+                        if let Some(tx) = &self.kbd_sender {
+                            let _ = tx.send(KeyboardBacklightRequest::Set(brightness));
+                        }
+                    }
+                }
+            }
+            Message::InitScreenBacklight(tx, screens) => {
                 let _ = tx.send(ScreenBacklightRequest::Get);
                 self.screen_sender = Some(tx);
-                self.screen_brightness = brightness;
+                self.screens = screens;
+            }
+            Message::UpdateScreenBrightness(screens) => {
+                self.screens = screens;
             }
-            Message::UpdateScreenBrightness(b) => {
-                self.screen_brightness = b;
+            Message::ScreenBrightnessChanged(index, screens) => {
+                self.screens = screens;
+                self.osd_screen = index;
+                return self.show_osd(OsdKind::Screen);
             }
-            Message::InitProfile(tx, profile) => {
+            Message::InitProfile(tx, status) => {
+                let _ = tx.send(PowerProfileRequest::GetChargeThresholds);
                 self.power_profile_sender.replace(tx);
-                self.power_profile = profile;
+                self.profile_status = status;
+                self.automatic_profile(None);
             }
-            Message::Profile(profile) => {
-                self.power_profile = profile;
+            Message::Profile(status) => {
+                self.profile_status = status;
                 if let Some(tx) = &self.kbd_sender {
                     let _ = tx.send(KeyboardBacklightRequest::Get);
                 }
@@ -215,7 +899,20 @@ impl Application for CosmicBatteryApplet {
     }
     fn view(&self, id: SurfaceIdWrapper) -> Element<Message> {
         match id {
-            SurfaceIdWrapper::LayerSurface(_) => unimplemented!(),
+            SurfaceIdWrapper::LayerSurface(_) => {
+                let (icon_name, brightness) = match self.osd_kind {
+                    OsdKind::Screen => (
+                        "display-brightness-symbolic",
+                        self.screens
+                            .get(self.osd_screen)
+                            .map_or(0.0, |s| s.brightness),
+                    ),
+                    OsdKind::Keyboard => ("keyboard-brightness-symbolic", self.kbd_brightness),
+                };
+                self.applet_helper
+                    .popup_container(osd::view(icon_name, brightness * 100.0, 100.0))
+                    .into()
+            }
             SurfaceIdWrapper::Window(_) => self
                 .applet_helper
                 .icon_button(&self.icon_name)
@@ -223,21 +920,7 @@ impl Application for CosmicBatteryApplet {
                 .into(),
             SurfaceIdWrapper::Popup(_) => {
                 let name = text(fl!("battery")).size(18);
-                let description = text(
-                    if "battery-full-charging-symbolic" == self.icon_name
-                        || "battery-full-charged-symbolic" == self.icon_name
-                    {
-                        format!("{}%", self.battery_percent)
-                    } else {
-                        format!(
-                            "{} {} ({:.0}%)",
-                            format_duration(self.time_remaining),
-                            fl!("until-empty"),
-                            self.battery_percent
-                        )
-                    },
-                )
-                .size(12);
+                let description = text(self.battery_description()).size(12);
                 self.applet_helper
                     .popup_container(
                         column![
@@ -246,7 +929,12 @@ impl Application for CosmicBatteryApplet {
                                     .style(Svg::Symbolic)
                                     .width(Length::Units(24))
                                     .height(Length::Units(24)),
-                                column![name, description]
+                                match self.critical_action_countdown() {
+                                    Some(countdown) => {
+                                        column![name, description, text(countdown).size(12)]
+                                    }
+                                    None => column![name, description],
+                                }
                             ]
                             .padding([0, 24])
                             .spacing(8)
@@ -254,98 +942,25 @@ impl Application for CosmicBatteryApplet {
                             container(divider::horizontal::light())
                                 .width(Length::Fill)
                                 .padding([0, 12]),
-                            button(APPLET_BUTTON_THEME)
-                                .custom(vec![row![
-                                    column![
-                                        text(fl!("battery")).size(14),
-                                        text(fl!("battery-desc")).size(12)
-                                    ]
-                                    .width(Length::Fill),
-                                    icon("emblem-ok-symbolic", 12).size(12).style(
-                                        match self.power_profile {
-                                            Power::Battery => Svg::SymbolicActive,
-                                            _ => Svg::Default,
-                                        }
-                                    ),
-                                ]
-                                .align_items(Alignment::Center)
-                                .into()])
-                                .padding([8, 24])
-                                .on_press(Message::SelectProfile(Power::Battery))
-                                .width(Length::Fill),
-                            button(APPLET_BUTTON_THEME)
-                                .custom(vec![row![
-                                    column![
-                                        text(fl!("balanced")).size(14),
-                                        text(fl!("balanced-desc")).size(12)
-                                    ]
-                                    .width(Length::Fill),
-                                    icon("emblem-ok-symbolic", 12).size(12).style(
-                                        match self.power_profile {
-                                            Power::Balanced => Svg::SymbolicActive,
-                                            _ => Svg::Default,
-                                        }
-                                    ),
-                                ]
-                                .align_items(Alignment::Center)
-                                .into()])
-                                .padding([8, 24])
-                                .on_press(Message::SelectProfile(Power::Balanced))
-                                .width(Length::Fill),
-                            button(APPLET_BUTTON_THEME)
-                                .custom(vec![row![
-                                    column![
-                                        text(fl!("performance")).size(14),
-                                        text(fl!("performance-desc")).size(12)
-                                    ]
-                                    .width(Length::Fill),
-                                    icon("emblem-ok-symbolic", 12).size(12).style(
-                                        match self.power_profile {
-                                            Power::Performance => Svg::SymbolicActive,
-                                            _ => Svg::Default,
-                                        }
-                                    ),
-                                ]
-                                .align_items(Alignment::Center)
-                                .into()])
-                                .padding([8, 24])
-                                .on_press(Message::SelectProfile(Power::Performance))
-                                .width(Length::Fill),
+                            self.devices(),
+                            self.profiles(),
                             container(divider::horizontal::light())
                                 .width(Length::Fill)
                                 .padding([0, 12]),
-                            container(toggler(fl!("max-charge"), self.charging_limit, |_| {
-                                Message::SetChargingLimit(!self.charging_limit)
-                            }))
-                            .padding([0, 24])
-                            .width(Length::Fill),
+                            self.charge_limit(),
+                            self.details(),
                             container(divider::horizontal::light())
                                 .width(Length::Fill)
                                 .padding([0, 12]),
-                            row![
-                                icon("display-brightness-symbolic", 24)
-                                    .style(Svg::Symbolic)
-                                    .width(Length::Units(24))
-                                    .height(Length::Units(24)),
-                                slider(
-                                    1..=100,
-                                    (self.screen_brightness * 100.0) as i32,
-                                    Message::SetScreenBrightness
-                                ),
-                                text(format!("{:.0}%", self.screen_brightness * 100.0))
-                                    .width(Length::Units(40))
-                                    .horizontal_alignment(Horizontal::Right)
-                            ]
-                            .padding([0, 24])
-                            .spacing(12),
+                            self.screen_sliders(),
                             row![
                                 icon("keyboard-brightness-symbolic", 24)
                                     .style(Svg::Symbolic)
                                     .width(Length::Units(24))
                                     .height(Length::Units(24)),
                                 slider(
-                                    0..=100,
-                                    (self.kbd_brightness * 100.0) as i32,
+                                    0..=self.kbd_max.max(1),
+                                    (self.kbd_brightness * self.kbd_max as f64).round() as i32,
                                     Message::SetKbdBrightness
                                 ),
                                 text(format!("{:.0}%", self.kbd_brightness * 100.0))
@@ -374,29 +989,72 @@ impl Application for CosmicBatteryApplet {
     }
 
     fn subscription(&self) -> Subscription<Message> {
+        // redraw the countdown of the critical action
+        let countdown = if self.critical_action_deadline.is_some() {
+            time::every(Duration::from_secs(1)).map(|_| Message::Tick)
+        } else {
+            Subscription::none()
+        };
+        // only watched while there is a keyboard backlight to turn off
+        let kbd_idle = match self.config.kbd_idle_timeout {
+            Some(timeout) if self.kbd_sender.is_some() => {
+                idle_subscription(0, Duration::from_secs(timeout.into()))
+                    .map(|(_, event)| Message::KbdIdle(event))
+            }
+            _ => Subscription::none(),
+        };
         Subscription::batch(vec![
+            countdown,
+            kbd_idle,
             device_subscription(0).map(|(_, event)| match event {
                 DeviceDbusEvent::Update {
                     icon_name,
                     percent,
                     time_to_empty,
+                    time_to_full,
+                    energy_rate,
+                    state,
+                    warning_level,
+                    on_battery,
+                    critical_action,
                 } => Message::Update {
                     icon_name,
                     percent,
                     time_to_empty,
+                    time_to_full,
+                    energy_rate,
+                    state,
+                    warning_level,
+                    on_battery,
+                    critical_action,
                 },
             }),
+            devices_subscription(0).map(|(_, event)| match event {
+                DevicesUpdate::Devices(devices) => Message::Devices(devices),
+                DevicesUpdate::Error(e) => Message::Errored(e),
+            }),
             kbd_backlight_subscription(0).map(|(_, event)| match event {
                 KeyboardBacklightUpdate::Update(b) => Message::UpdateKbdBrightness(b),
-                KeyboardBacklightUpdate::Init(tx, b) => Message::InitKbdBacklight(tx, b),
+                KeyboardBacklightUpdate::Changed(b) => Message::KbdBrightnessChanged(b),
+                KeyboardBacklightUpdate::Init(tx, b, max) => Message::InitKbdBacklight(tx, b, max),
             }),
             screen_backlight_subscription(0).map(|(_, event)| match event {
                 ScreenBacklightUpdate::Update(b) => Message::UpdateScreenBrightness(b),
+                ScreenBacklightUpdate::Changed(index, b) => {
+                    Message::ScreenBrightnessChanged(index, b)
+                }
                 ScreenBacklightUpdate::Init(tx, b) => Message::InitScreenBacklight(tx, b),
             }),
             power_profile_subscription(0).map(|(_, event)| match event {
-                PowerProfileUpdate::Update { profile } => Message::Profile(profile),
-                PowerProfileUpdate::Init(tx, p) => Message::InitProfile(p, tx),
+                PowerProfileUpdate::Update(status) => Message::Profile(status),
+                PowerProfileUpdate::ChargeThresholds {
+                    profiles,
+                    thresholds,
+                } => Message::ChargeThresholds(profiles, thresholds),
+                PowerProfileUpdate::ChargeThresholdsUnsupported => {
+                    Message::ChargeThresholdsUnsupported
+                }
+                PowerProfileUpdate::Init(status, tx) => Message::InitProfile(tx, status),
                 PowerProfileUpdate::Error(e) => Message::Errored(e), // TODO: handle error
             }),
         ])
@@ -417,3 +1075,46 @@ impl Application for CosmicBatteryApplet {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_battery_before_init() {
+        let mut applet = CosmicBatteryApplet {
+            config: BatteryConfig {
+                power_saver_below: Some(20),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let _ = applet.update(Message::Update {
+            icon_name: String::new(),
+            percent: 10.0,
+            time_to_empty: 0,
+            time_to_full: 0,
+            energy_rate: 0.0,
+            state: BatteryState::Discharging,
+            warning_level: WarningLevel::None,
+            on_battery: true,
+            critical_action: String::new(),
+        });
+        assert!(!applet.switched_to_power_saver);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let _ = applet.update(Message::InitProfile(tx, ProfileStatus::default()));
+        let mut requests = Vec::new();
+        while let Ok(request) = rx.try_recv() {
+            requests.push(request);
+        }
+        assert!(requests
+            .iter()
+            .any(|r| matches!(r, PowerProfileRequest::Set(Power::Battery))));
+        assert!(applet.switched_to_power_saver);
+
+        // it is only switched once
+        applet.automatic_profile(None);
+        assert!(rx.try_recv().is_err());
+    }
+}
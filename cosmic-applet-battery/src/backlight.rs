@@ -1,21 +1,31 @@
-// TODO: use udev to monitor for brightness changes?
 // How should key bindings be handled? Need something like gnome-settings-daemon?
 
 use std::{
     fmt::Debug,
     fs::File,
     hash::Hash,
-    io::{self, Read},
-    os::unix::ffi::OsStrExt,
+    io::{self, Read, Seek},
+    os::unix::{ffi::OsStrExt, io::AsRawFd, net::UnixStream},
     path::Path,
     str::{self, FromStr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use cosmic::iced;
+use ddc_hi::{Ddc, Display};
 use iced::subscription;
+use nix::poll::{poll, PollFd, PollFlags};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 const BACKLIGHT_SYSDIR: &str = "/sys/class/backlight";
+// MCCS VCP code of the luminance
+const VCP_BRIGHTNESS: u8 = 0x10;
+// Raw backlights control the luminance linearly, which isn't how it is perceived
+const GAMMA: f64 = 2.2;
+// Changes notified this long after setting the brightness are taken as our own,
+// dragging the slider sets it many times and the notifications lag behind
+const OWN_CHANGE_WINDOW: Duration = Duration::from_millis(500);
 
 #[zbus::dbus_proxy(
     default_service = "org.freedesktop.login1",
@@ -27,16 +37,26 @@ trait LogindSession {
 }
 
 #[derive(Clone)]
-pub struct Backlight(String);
+pub struct Backlight {
+    name: String,
+    max_brightness: u32,
+    // the slider is gamma corrected for raw backlights, firmware ones already are
+    raw: bool,
+}
 
 impl Backlight {
-    pub async fn brightness(&self) -> Option<u32> {
-        self.prop("brightness").await
+    async fn new(name: String) -> Option<Self> {
+        let max_brightness = prop(&name, "max_brightness").await?;
+        let raw = prop::<String>(&name, "type").await.as_deref() == Some("raw");
+        Some(Backlight {
+            name,
+            max_brightness,
+            raw,
+        })
     }
 
-    // XXX cache value. Async?
-    pub async fn max_brightness(&self) -> Option<u32> {
-        self.prop("max_brightness").await
+    pub async fn brightness(&self) -> Option<u32> {
+        prop(&self.name, "actual_brightness").await
     }
 
     pub async fn set_brightness(
@@ -44,35 +64,277 @@ impl Backlight {
         session: &LogindSessionProxy<'_>,
         value: u32,
     ) -> zbus::Result<()> {
-        session.set_brightness("backlight", &self.0, value).await
+        session.set_brightness("backlight", &self.name, value).await
+    }
+
+    /// Slider position between 0 and 1 of a brightness
+    fn slider_position(&self, value: u32) -> f64 {
+        let value = (value as f64 / self.max_brightness.max(1) as f64).clamp(0., 1.);
+        if self.raw {
+            value.powf(1. / GAMMA)
+        } else {
+            value
+        }
+    }
+
+    /// Brightness of a slider position, never turning the backlight off
+    fn slider_brightness(&self, value: f64) -> u32 {
+        let value = value.clamp(0., 1.);
+        let value = if self.raw { value.powf(GAMMA) } else { value };
+        ((value * self.max_brightness as f64).round() as u32).max(1)
+    }
+
+    // Backlights of a panel are children of its DRM connector, like `card0-eDP-1`
+    async fn connector(&self) -> Option<String> {
+        let device =
+            tokio::fs::read_link(Path::new(BACKLIGHT_SYSDIR).join(&self.name).join("device"))
+                .await
+                .ok()?;
+        let device = device.file_name()?.to_str()?;
+        match device.split_once('-') {
+            Some((card, connector)) if card.starts_with("card") => Some(connector.to_string()),
+            _ => None,
+        }
     }
 
-    async fn prop<T: FromStr>(&self, name: &str) -> Option<T> {
-        let path = Path::new(BACKLIGHT_SYSDIR).join(&self.0).join(name);
-        let mut file = File::open(path).ok()?;
-        let mut s = String::new();
-        file.read_to_string(&mut s).ok()?;
-        s.trim().parse().ok()
+    // The backlight core calls sysfs_notify() on `actual_brightness` for every change,
+    // made by hotkeys as well as by other programs, which wakes up poll().
+    // The thread stops once the other end of `stop` is dropped.
+    fn watch(&self, index: usize, tx: UnboundedSender<usize>, stop: UnixStream) {
+        let path = Path::new(BACKLIGHT_SYSDIR)
+            .join(&self.name)
+            .join("actual_brightness");
+        std::thread::spawn(move || {
+            let mut file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    log::warn!("Failed to watch {}: {}", path.display(), e);
+                    return;
+                }
+            };
+            let mut s = String::new();
+            loop {
+                // the attribute has to be read before each poll
+                s.clear();
+                if file
+                    .rewind()
+                    .and_then(|_| file.read_to_string(&mut s))
+                    .is_err()
+                {
+                    return;
+                }
+                let mut fds = [
+                    PollFd::new(file.as_raw_fd(), PollFlags::POLLPRI | PollFlags::POLLERR),
+                    PollFd::new(stop.as_raw_fd(), PollFlags::POLLIN),
+                ];
+                if poll(&mut fds, -1).is_err() {
+                    return;
+                }
+                let stopped = fds[1].revents().map_or(false, |events| !events.is_empty());
+                if stopped || tx.send(index).is_err() {
+                    return;
+                }
+            }
+        });
     }
 }
 
-// Choose backlight with most "precision". This is what `light` does.
-pub async fn backlight() -> io::Result<Option<Backlight>> {
+async fn prop<T: FromStr>(name: &str, attribute: &str) -> Option<T> {
+    let path = Path::new(BACKLIGHT_SYSDIR).join(name).join(attribute);
+    let s = tokio::fs::read_to_string(path).await.ok()?;
+    s.trim().parse().ok()
+}
+
+/// Backlights with the name of their connector, if they have one.
+///
+/// There is one for each panel attached to a connector. Platform and firmware
+/// backlights which aren't attached to one are only used without those, choosing
+/// the one with most "precision". This is what `light` does.
+pub async fn backlights() -> io::Result<Vec<(Backlight, Option<String>)>> {
+    let mut connected = Vec::new();
     let mut best_backlight = None;
     let mut best_max_brightness = 0;
     let mut dir_stream = tokio::fs::read_dir(BACKLIGHT_SYSDIR).await?;
     while let Ok(Some(entry)) = dir_stream.next_entry().await {
         if let Ok(filename) = str::from_utf8(entry.file_name().as_bytes()) {
-            let backlight = Backlight(filename.to_string());
-            if let Some(max_brightness) = backlight.max_brightness().await {
-                if max_brightness > best_max_brightness {
-                    best_backlight = Some(backlight);
-                    best_max_brightness = max_brightness;
+            let backlight = match Backlight::new(filename.to_string()).await {
+                Some(backlight) => backlight,
+                None => continue,
+            };
+            if let Some(connector) = backlight.connector().await {
+                connected.push((backlight, Some(connector)));
+            } else if backlight.max_brightness > best_max_brightness {
+                best_max_brightness = backlight.max_brightness;
+                best_backlight = Some(backlight);
+            }
+        }
+    }
+    if connected.is_empty() {
+        Ok(best_backlight.into_iter().map(|b| (b, None)).collect())
+    } else {
+        connected.sort_by(|a, b| a.1.cmp(&b.1));
+        Ok(connected)
+    }
+}
+
+/// External monitors which support setting their brightness over DDC/CI,
+/// with their current and maximum brightness
+pub async fn ddc_displays() -> Vec<(Display, String, u16, u16)> {
+    // DDC/CI is slow, and ddc-hi is blocking
+    tokio::task::spawn_blocking(|| {
+        Display::enumerate()
+            .into_iter()
+            .filter_map(|mut display| {
+                let value = display.handle.get_vcp_feature(VCP_BRIGHTNESS).ok()?;
+                let name = display
+                    .info
+                    .model_name
+                    .clone()
+                    .unwrap_or_else(|| display.info.id.clone());
+                Some((display, name, value.value(), value.maximum()))
+            })
+            .collect()
+    })
+    .await
+    .unwrap_or_default()
+}
+
+enum ScreenDevice {
+    // with when the brightness was last set, to tell our changes from others
+    Backlight(Backlight, Option<Instant>),
+    // a read takes tens to hundreds of milliseconds, so the brightness is kept
+    // and only read again by `refresh`
+    Ddc {
+        display: Arc<Mutex<Display>>,
+        max: u16,
+        brightness: f64,
+    },
+}
+
+/// An internal panel or external monitor with adjustable brightness
+pub struct Screen {
+    name: String,
+    device: ScreenDevice,
+}
+
+impl Screen {
+    pub async fn screens() -> Vec<Screen> {
+        let mut screens: Vec<_> = backlights()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(backlight, connector)| Screen {
+                name: connector.unwrap_or_else(|| backlight.name.clone()),
+                device: ScreenDevice::Backlight(backlight, None),
+            })
+            .collect();
+        screens.extend(
+            ddc_displays()
+                .await
+                .into_iter()
+                .map(|(display, name, value, max)| Screen {
+                    name,
+                    device: ScreenDevice::Ddc {
+                        display: Arc::new(Mutex::new(display)),
+                        max,
+                        brightness: ddc_brightness(value, max),
+                    },
+                }),
+        );
+        screens
+    }
+
+    /// Brightness between 0 and 1, as last read for DDC/CI monitors
+    pub async fn brightness(&self) -> Option<f64> {
+        match &self.device {
+            ScreenDevice::Backlight(backlight, _) => {
+                Some(backlight.slider_position(backlight.brightness().await?))
+            }
+            ScreenDevice::Ddc { brightness, .. } => Some(*brightness),
+        }
+    }
+
+    /// Read the brightness of a DDC/CI monitor again
+    pub async fn refresh(&mut self) {
+        if let ScreenDevice::Ddc {
+            display,
+            max,
+            brightness,
+        } = &mut self.device
+        {
+            let display = display.clone();
+            let value = tokio::task::spawn_blocking(move || {
+                display
+                    .lock()
+                    .ok()?
+                    .handle
+                    .get_vcp_feature(VCP_BRIGHTNESS)
+                    .ok()
+            })
+            .await
+            .ok()
+            .flatten();
+            if let Some(value) = value {
+                *brightness = ddc_brightness(value.value(), *max);
+            }
+        }
+    }
+
+    pub async fn set_brightness(&mut self, session: &LogindSessionProxy<'_>, value: f64) {
+        match &mut self.device {
+            ScreenDevice::Backlight(backlight, set) => {
+                let value = backlight.slider_brightness(value);
+                let _ = backlight.set_brightness(session, value).await;
+                *set = Some(Instant::now());
+            }
+            ScreenDevice::Ddc {
+                display,
+                max,
+                brightness,
+            } => {
+                let display = display.clone();
+                let value = (value.clamp(0., 1.) * (*max as f64)).round() as u16;
+                let set = tokio::task::spawn_blocking(move || {
+                    let mut display = display.lock().ok()?;
+                    display
+                        .handle
+                        .set_vcp_feature(VCP_BRIGHTNESS, value)
+                        .map_err(|e| log::warn!("Failed to set brightness over DDC/CI: {}", e))
+                        .ok()
+                })
+                .await;
+                if let Ok(Some(())) = set {
+                    *brightness = ddc_brightness(value, *max);
                 }
             }
         }
     }
-    Ok(best_backlight)
+
+    // Whether the brightness was changed by something else than `set_brightness`.
+    // DDC/CI monitors don't notify changes, they are only watched for backlights.
+    fn changed_externally(&self) -> bool {
+        match &self.device {
+            ScreenDevice::Backlight(_, set) => {
+                set.map_or(true, |set| set.elapsed() > OWN_CHANGE_WINDOW)
+            }
+            ScreenDevice::Ddc { .. } => false,
+        }
+    }
+
+    async fn status(screens: &[Screen]) -> Vec<ScreenBrightness> {
+        let mut status = Vec::with_capacity(screens.len());
+        for screen in screens {
+            status.push(ScreenBrightness {
+                name: screen.name.clone(),
+                brightness: screen.brightness().await.unwrap_or_default(),
+            });
+        }
+        status
+    }
+}
+
+fn ddc_brightness(value: u16, max: u16) -> f64 {
+    (value as f64 / max.max(1) as f64).clamp(0., 1.)
 }
 
 pub fn screen_backlight_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
@@ -84,9 +346,12 @@ pub fn screen_backlight_subscription<I: 'static + Hash + Copy + Send + Sync + De
 pub enum State {
     Ready,
     Waiting(
-        Backlight,
+        Vec<Screen>,
         LogindSessionProxy<'static>,
         UnboundedReceiver<ScreenBacklightRequest>,
+        UnboundedReceiver<usize>,
+        // dropped with the subscription to stop the threads watching the backlights
+        UnixStream,
     ),
     Finished,
 }
@@ -105,75 +370,101 @@ async fn start_listening<I: Copy>(
                 Ok(p) => p,
                 Err(_) => return (None, State::Finished),
             };
-            let backlight = match backlight().await {
-                Ok(Some(b)) => b,
-                _ => return (None, State::Finished),
-            };
+            let screens = Screen::screens().await;
+            if screens.is_empty() {
+                return (None, State::Finished);
+            }
             let (tx, rx) = unbounded_channel();
+            let (changes_tx, changes) = unbounded_channel();
+            let (stop, stopped) = match UnixStream::pair() {
+                Ok(pair) => pair,
+                Err(_) => return (None, State::Finished),
+            };
+            for (index, screen) in screens.iter().enumerate() {
+                if let ScreenDevice::Backlight(backlight, _) = &screen.device {
+                    match stopped.try_clone() {
+                        Ok(stopped) => backlight.watch(index, changes_tx.clone(), stopped),
+                        Err(e) => log::warn!("Failed to watch {}: {}", backlight.name, e),
+                    }
+                }
+            }
 
-            let b = (backlight.brightness().await.unwrap_or_default() as f64
-                / backlight.max_brightness().await.unwrap_or(1) as f64)
-                .clamp(0., 1.);
+            let status = Screen::status(&screens).await;
             (
-                Some((id, ScreenBacklightUpdate::Init(tx, b))),
-                State::Waiting(backlight, screen_proxy, rx),
+                Some((id, ScreenBacklightUpdate::Init(tx, status))),
+                State::Waiting(screens, screen_proxy, rx, changes, stop),
             )
         }
-        State::Waiting(backlight, proxy, mut rx) => match rx.recv().await {
-            Some(req) => match req {
-                ScreenBacklightRequest::Get => {
-                    let msg = if let Some(max_brightness) = backlight.max_brightness().await {
-                        let value = (backlight.brightness().await.unwrap_or_default() as f64
-                            / max_brightness as f64)
-                            .clamp(0., 1.);
-                        Some((id, ScreenBacklightUpdate::Update(value)))
+        State::Waiting(mut screens, proxy, mut rx, mut changes, stop) => {
+            tokio::select! {
+                req = rx.recv() => match req {
+                    Some(ScreenBacklightRequest::Get) => {
+                        let status = Screen::status(&screens).await;
+                        (
+                            Some((id, ScreenBacklightUpdate::Update(status))),
+                            State::Waiting(screens, proxy, rx, changes, stop),
+                        )
+                    }
+                    Some(ScreenBacklightRequest::Refresh) => {
+                        for screen in &mut screens {
+                            screen.refresh().await;
+                        }
+                        let status = Screen::status(&screens).await;
+                        (
+                            Some((id, ScreenBacklightUpdate::Update(status))),
+                            State::Waiting(screens, proxy, rx, changes, stop),
+                        )
+                    }
+                    Some(ScreenBacklightRequest::Set(index, value)) => {
+                        if let Some(screen) = screens.get_mut(index) {
+                            screen.set_brightness(&proxy, value).await;
+                        }
+                        (None, State::Waiting(screens, proxy, rx, changes, stop))
+                    }
+                    None => (None, State::Finished),
+                },
+                Some(index) = changes.recv() => {
+                    let changed = screens
+                        .get(index)
+                        .map_or(false, |screen| screen.changed_externally());
+                    let msg = if changed {
+                        let status = Screen::status(&screens).await;
+                        Some((id, ScreenBacklightUpdate::Changed(index, status)))
                     } else {
                         None
                     };
-                    (msg, State::Waiting(backlight, proxy, rx))
-                }
-                ScreenBacklightRequest::Set(value) => {
-                    if let Some(max_brightness) = backlight.max_brightness().await {
-                        let value = value.clamp(0., 1.) * (max_brightness as f64);
-                        let value = value.round() as u32;
-                        let _ = backlight.set_brightness(&proxy, value).await;
-                    }
-                    (None, State::Waiting(backlight, proxy, rx))
+                    (msg, State::Waiting(screens, proxy, rx, changes, stop))
                 }
-            },
-            None => (None, State::Finished),
-        },
+            }
+        }
         State::Finished => iced::futures::future::pending().await,
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenBrightness {
+    pub name: String,
+    pub brightness: f64,
+}
+
 #[derive(Debug, Clone)]
 pub enum ScreenBacklightUpdate {
-    Update(f64),
-    Init(UnboundedSender<ScreenBacklightRequest>, f64),
+    Update(Vec<ScreenBrightness>),
+    /// The brightness of the screen at an index was changed by something else
+    Changed(usize, Vec<ScreenBrightness>),
+    Init(
+        UnboundedSender<ScreenBacklightRequest>,
+        Vec<ScreenBrightness>,
+    ),
 }
 
+/// Requests for the screens, which are identified by their index
 #[derive(Debug, Clone)]
 pub enum ScreenBacklightRequest {
     Get,
-    Set(f64),
-}
-
-/*
-// TODO: Cache device, max_brightness, etc.
-async fn set_display_brightness(brightness: f64) -> io::Result<()> {
-    if let Some(backlight) = backlight()? {
-        if let Some(max_brightness) = backlight.max_brightness() {
-            let value = brightness.clamp(0., 1.) * (max_brightness as f64);
-            let value = value.round() as u32;
-            let connection = zbus::Connection::system().await?;
-            if let Ok(session) = LogindSessionProxy::builder(&connection).build().await {
-                backlight.set_brightness(&session, value).await;
-            }
-        }
-    }
-    Ok(())
+    /// Get, reading the brightness of DDC/CI monitors again
+    Refresh,
+    Set(usize, f64),
 }
-*/
 
 // TODO: keyboard backlight
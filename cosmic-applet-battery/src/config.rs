@@ -1,3 +1,65 @@
-pub const APP_ID: &str = "com.system76.CosmicAppletButton";
+use crate::power_daemon::Power;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::PathBuf;
+use xdg::BaseDirectories;
+
+pub const APP_ID: &str = "com.system76.CosmicAppletBattery";
 pub const PROFILE: &str = "";
 pub const VERSION: &str = "0.1.0";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BatteryConfig {
+    /// Battery percentages at which a low battery notification is sent
+    pub warning_thresholds: Vec<u8>,
+    /// Seconds counted down in the popup before the critical action is taken
+    ///
+    /// This is only what the applet shows. UPower doesn't make its delay
+    /// available and takes the action after it on its own, 20 seconds unless it
+    /// was built with a different one.
+    pub critical_action_delay: u32,
+    /// Power profile switched to when the power adapter is unplugged
+    pub on_battery_profile: Option<Power>,
+    /// Power profile switched to when the power adapter is plugged in
+    pub on_ac_profile: Option<Power>,
+    /// Battery percentage below which the battery profile is switched to
+    pub power_saver_below: Option<u8>,
+    /// Seconds of inactivity after which the keyboard backlight is turned off
+    pub kbd_idle_timeout: Option<u32>,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            warning_thresholds: vec![20, 10, 5],
+            critical_action_delay: 20,
+            on_battery_profile: None,
+            on_ac_profile: None,
+            power_saver_below: None,
+            kbd_idle_timeout: None,
+        }
+    }
+}
+
+impl BatteryConfig {
+    /// load the config, falling back to the defaults
+    pub fn load() -> anyhow::Result<BatteryConfig> {
+        let mut relative_path = PathBuf::from(APP_ID);
+        relative_path.push("config.ron");
+        let file = match BaseDirectories::new()
+            .ok()
+            .and_then(|dirs| dirs.find_config_file(relative_path))
+            .and_then(|p| File::open(p).ok())
+        {
+            Some(path) => path,
+            _ => {
+                anyhow::bail!("Failed to load config");
+            }
+        };
+
+        ron::de::from_reader::<_, BatteryConfig>(file)
+            .map_err(|err| anyhow!("Failed to parse config file: {}", err))
+    }
+}
@@ -0,0 +1,137 @@
+//! # Idle notifications from the compositor
+//!
+//! Uses the `ext-idle-notify-v1` protocol on a separate Wayland connection, which is
+//! dispatched on its own thread.
+
+use cosmic::iced;
+use cosmic::iced_native::subscription;
+use sctk::reexports::client::{
+    delegate_noop,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, QueueHandle,
+};
+use sctk::reexports::protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+use std::{fmt::Debug, hash::Hash, time::Duration};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+struct AppData {
+    tx: UnboundedSender<IdleUpdate>,
+    exit: bool,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for AppData {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as sctk::reexports::client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let update = match event {
+            ext_idle_notification_v1::Event::Idled => IdleUpdate::Idle,
+            ext_idle_notification_v1::Event::Resumed => IdleUpdate::Resumed,
+            _ => return,
+        };
+        // stop once the subscription is gone
+        state.exit = state.tx.send(update).is_err();
+    }
+}
+
+delegate_noop!(AppData: ignore WlSeat);
+delegate_noop!(AppData: ExtIdleNotifierV1);
+
+fn idle_handler(timeout: Duration, tx: UnboundedSender<IdleUpdate>) {
+    let conn = match Connection::connect_to_env() {
+        Ok(conn) => conn,
+        Err(e) => {
+            log::warn!("Failed to connect to the compositor: {}", e);
+            return;
+        }
+    };
+    let (globals, mut event_queue) = match registry_queue_init::<AppData>(&conn) {
+        Ok(registry) => registry,
+        Err(e) => {
+            log::warn!("Failed to get the globals: {}", e);
+            return;
+        }
+    };
+    let qh = event_queue.handle();
+    let (notifier, seat) = match (
+        globals.bind::<ExtIdleNotifierV1, _, _>(&qh, 1..=1, ()),
+        globals.bind::<WlSeat, _, _>(&qh, 1..=1, ()),
+    ) {
+        (Ok(notifier), Ok(seat)) => (notifier, seat),
+        _ => {
+            log::warn!("The compositor doesn't support idle notifications");
+            return;
+        }
+    };
+    let timeout = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+    let _notification = notifier.get_idle_notification(timeout, &seat, &qh, ());
+
+    let mut app_data = AppData { tx, exit: false };
+    while !app_data.exit {
+        if event_queue.blocking_dispatch(&mut app_data).is_err() {
+            break;
+        }
+    }
+}
+
+pub fn idle_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
+    id: I,
+    timeout: Duration,
+) -> iced::Subscription<(I, IdleUpdate)> {
+    subscription::unfold(id, State::Ready, move |state| {
+        start_listening(id, timeout, state)
+    })
+}
+
+pub enum State {
+    Ready,
+    Waiting(UnboundedReceiver<IdleUpdate>),
+    Finished,
+}
+
+async fn start_listening<I: Copy>(
+    id: I,
+    timeout: Duration,
+    state: State,
+) -> (Option<(I, IdleUpdate)>, State) {
+    match state {
+        State::Ready => {
+            let (tx, rx) = unbounded_channel();
+            std::thread::spawn(move || idle_handler(timeout, tx));
+            (None, State::Waiting(rx))
+        }
+        State::Waiting(mut rx) => match rx.recv().await {
+            Some(update) => (Some((id, update)), State::Waiting(rx)),
+            None => (None, State::Finished),
+        },
+        State::Finished => iced::futures::future::pending().await,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum IdleUpdate {
+    /// The user has been idle for the timeout
+    Idle,
+    /// The user is active again
+    Resumed,
+}
@@ -2,11 +2,15 @@
 mod backlight;
 mod app;
 mod config;
+mod idle;
 mod localize;
+mod notifications;
 mod power_daemon;
+mod power_profiles;
 mod upower;
 
 mod upower_device;
+mod upower_devices;
 mod upower_kbdbacklight;
 use config::APP_ID;
 use log::info;
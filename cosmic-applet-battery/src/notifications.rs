@@ -0,0 +1,52 @@
+//! # DBus interface proxy for: `org.freedesktop.Notifications`
+
+use std::collections::HashMap;
+use zbus::{dbus_proxy, zvariant::Value, Connection};
+
+#[dbus_proxy(
+    default_service = "org.freedesktop.Notifications",
+    interface = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    /// Notify method
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+/// Send a notification, replacing the one with id `replaces_id` if it isn't 0
+pub async fn notify(
+    replaces_id: u32,
+    icon_name: &str,
+    summary: &str,
+    body: &str,
+    critical: bool,
+) -> zbus::Result<u32> {
+    let connection = Connection::session().await?;
+    let notifications = NotificationsProxy::new(&connection).await?;
+    let mut hints = HashMap::new();
+    // 2 is the critical urgency, which isn't dismissed automatically
+    hints.insert("urgency", Value::U8(if critical { 2 } else { 1 }));
+    notifications
+        .notify(
+            "Battery",
+            replaces_id,
+            icon_name,
+            summary,
+            body,
+            &[],
+            hints,
+            -1,
+        )
+        .await
+}
@@ -18,12 +18,17 @@
 //!
 //! …consequently `zbus-xmlgen` did not generate code for the above interfaces.
 
+use crate::power_profiles::PowerProfilesProxy;
 use cosmic::iced;
 use cosmic::iced_native::subscription;
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
+use zbus::zvariant::{OwnedValue, Value};
 use zbus::Result;
 use zbus::{dbus_proxy, Connection};
 
@@ -85,7 +90,7 @@ trait PowerDaemon {
     fn power_profile_switch(&self, profile: &str) -> zbus::Result<()>;
 }
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Deserialize, Serialize)]
 pub enum Power {
     Battery,
     #[default]
@@ -93,13 +98,33 @@ pub enum Power {
     Performance,
 }
 
+impl Power {
+    const ALL: [Power; 3] = [Power::Battery, Power::Balanced, Power::Performance];
+
+    /// Name of the profile in power-profiles-daemon
+    fn ppd_name(&self) -> &'static str {
+        match self {
+            Power::Battery => "power-saver",
+            Power::Balanced => "balanced",
+            Power::Performance => "performance",
+        }
+    }
+
+    fn from_ppd_name(name: &str) -> Option<Self> {
+        Power::ALL.into_iter().find(|p| p.ppd_name() == name)
+    }
+}
+
 pub async fn get_power_profile(daemon: PowerDaemonProxy<'_>) -> Result<Power> {
     let power = daemon.get_profile().await?;
     match power.as_str() {
         "Battery" => Ok(Power::Battery),
         "Balanced" => Ok(Power::Balanced),
         "Performance" => Ok(Power::Performance),
-        _ => panic!("Unknown power profile: {}", power),
+        _ => Err(zbus::Error::Failure(format!(
+            "Unknown power profile: {}",
+            power
+        ))),
     }
 }
 
@@ -111,16 +136,192 @@ pub async fn set_power_profile(daemon: PowerDaemonProxy<'_>, power: Power) -> Re
     }
 }
 
+/// Charge thresholds preset of the daemon, like "Balanced" with 86-90%
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChargeProfile {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub start: u8,
+    pub end: u8,
+}
+
+impl ChargeProfile {
+    fn from_dict(dict: &HashMap<String, OwnedValue>) -> Option<Self> {
+        let string = |key: &str| match dict.get(key).map(|v| &**v) {
+            Some(Value::Str(s)) => Some(s.to_string()),
+            _ => None,
+        };
+        let percent = |key: &str| match dict.get(key).map(|v| &**v) {
+            Some(Value::U8(p)) => Some(*p),
+            _ => None,
+        };
+        Some(ChargeProfile {
+            id: string("id")?,
+            title: string("title")?,
+            description: string("description").unwrap_or_default(),
+            start: percent("start")?,
+            end: percent("end")?,
+        })
+    }
+
+    pub fn thresholds(&self) -> (u8, u8) {
+        (self.start, self.end)
+    }
+}
+
+pub async fn get_charge_profiles(daemon: &PowerDaemonProxy<'_>) -> Result<Vec<ChargeProfile>> {
+    Ok(daemon
+        .get_charge_profiles()
+        .await?
+        .iter()
+        .filter_map(ChargeProfile::from_dict)
+        .collect())
+}
+
+/// An application holding a profile through power-profiles-daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileHold {
+    pub application_id: String,
+    pub reason: String,
+    pub profile: Power,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileStatus {
+    pub profile: Power,
+    /// profiles supported by the backend
+    pub profiles: Vec<Power>,
+    /// why the performance profile is degraded, if it is
+    pub degraded: Option<String>,
+    pub holds: Vec<ProfileHold>,
+}
+
+fn dict_string(dict: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    match dict.get(key).map(|v| &**v) {
+        Some(Value::Str(s)) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// system76-power, or power-profiles-daemon when it isn't running
+#[derive(Debug, Clone)]
+pub enum Backend {
+    System76(PowerDaemonProxy<'static>),
+    PowerProfiles(PowerProfilesProxy<'static>),
+}
+
+impl Backend {
+    async fn new(conn: &Connection) -> Result<Self> {
+        let daemon = PowerDaemonProxy::new(conn).await?;
+        let system76_error = match daemon.get_profile().await {
+            Ok(_) => return Ok(Backend::System76(daemon)),
+            Err(e) => e,
+        };
+        let power_profiles = PowerProfilesProxy::new(conn).await?;
+        match power_profiles.active_profile().await {
+            Ok(_) => Ok(Backend::PowerProfiles(power_profiles)),
+            Err(e) => {
+                log::warn!("system76-power is not available: {}", system76_error);
+                Err(e)
+            }
+        }
+    }
+
+    async fn status(&self) -> Result<ProfileStatus> {
+        match self {
+            Backend::System76(daemon) => Ok(ProfileStatus {
+                profile: get_power_profile(daemon.clone()).await?,
+                profiles: Power::ALL.to_vec(),
+                degraded: None,
+                holds: Vec::new(),
+            }),
+            Backend::PowerProfiles(power_profiles) => {
+                let active = power_profiles.active_profile().await?;
+                let profiles = power_profiles
+                    .profiles()
+                    .await?
+                    .iter()
+                    .filter_map(|p| dict_string(p, "Profile"))
+                    .filter_map(|name| Power::from_ppd_name(&name))
+                    .collect();
+                let holds = power_profiles
+                    .active_profile_holds()
+                    .await
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|hold| {
+                        Some(ProfileHold {
+                            application_id: dict_string(hold, "ApplicationId")?,
+                            reason: dict_string(hold, "Reason").unwrap_or_default(),
+                            profile: Power::from_ppd_name(&dict_string(hold, "Profile")?)?,
+                        })
+                    })
+                    .collect();
+                Ok(ProfileStatus {
+                    profile: Power::from_ppd_name(&active).unwrap_or_default(),
+                    profiles,
+                    degraded: power_profiles
+                        .performance_degraded()
+                        .await
+                        .ok()
+                        .filter(|reason| !reason.is_empty()),
+                    holds,
+                })
+            }
+        }
+    }
+
+    /// Changes of the status, including the ones made by other programs
+    async fn changes(&self) -> Result<BoxStream<'static, ()>> {
+        match self {
+            Backend::System76(daemon) => Ok(daemon
+                .receive_power_profile_switch()
+                .await?
+                .map(|_| ())
+                .boxed()),
+            Backend::PowerProfiles(power_profiles) => Ok(futures::stream_select!(
+                power_profiles
+                    .receive_active_profile_changed()
+                    .await
+                    .map(|_| ()),
+                power_profiles.receive_profiles_changed().await.map(|_| ()),
+                power_profiles
+                    .receive_performance_degraded_changed()
+                    .await
+                    .map(|_| ()),
+                power_profiles
+                    .receive_active_profile_holds_changed()
+                    .await
+                    .map(|_| ()),
+            )
+            .boxed()),
+        }
+    }
+
+    async fn set_profile(&self, power: Power) -> Result<()> {
+        match self {
+            Backend::System76(daemon) => set_power_profile(daemon.clone(), power).await,
+            Backend::PowerProfiles(power_profiles) => {
+                power_profiles.set_active_profile(power.ppd_name()).await
+            }
+        }
+    }
+}
+
 pub fn power_profile_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
     id: I,
 ) -> iced::Subscription<(I, PowerProfileUpdate)> {
     subscription::unfold(id, State::Ready, move |state| start_listening(id, state))
 }
 
-#[derive(Debug)]
 pub enum State {
     Ready,
-    Waiting(Connection, UnboundedReceiver<PowerProfileRequest>),
+    Waiting(
+        Backend,
+        UnboundedReceiver<PowerProfileRequest>,
+        BoxStream<'static, ()>,
+    ),
     Finished,
 }
 
@@ -131,73 +332,91 @@ async fn start_listening<I: Copy>(id: I, state: State) -> (Option<(I, PowerProfi
                 Ok(conn) => conn,
                 Err(e) => return (Some((id, PowerProfileUpdate::Error(e))), State::Finished),
             };
+            let backend = match Backend::new(&conn).await.map_err(|e| e.to_string()) {
+                Ok(backend) => backend,
+                Err(e) => return (Some((id, PowerProfileUpdate::Error(e))), State::Finished),
+            };
+            let changes = match backend.changes().await.map_err(|e| e.to_string()) {
+                Ok(changes) => changes,
+                Err(e) => return (Some((id, PowerProfileUpdate::Error(e))), State::Finished),
+            };
             let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
 
-            let power_proxy = match PowerDaemonProxy::new(&conn)
-                .await
-                .map_err(|e| e.to_string())
-            {
-                Ok(p) => p,
-                Err(e) => {
-                    return (
-                        Some((id, PowerProfileUpdate::Error(e))),
-                        State::Waiting(conn, rx),
-                    )
-                }
-            };
-            let profile = match get_power_profile(power_proxy)
-                .await
-                .map_err(|e| e.to_string())
-            {
-                Ok(p) => p,
+            // the applet still needs the sender if the status can't be read yet,
+            // it is asked for again right away
+            let status = match backend.status().await {
+                Ok(status) => status,
                 Err(e) => {
-                    return (
-                        Some((id, PowerProfileUpdate::Error(e))),
-                        State::Waiting(conn, rx),
-                    )
+                    log::error!("Failed to get power profile status: {}", e);
+                    let _ = tx.send(PowerProfileRequest::Get);
+                    ProfileStatus::default()
                 }
             };
 
             (
-                Some((id, PowerProfileUpdate::Init(profile, tx))),
-                State::Waiting(conn, rx),
+                Some((id, PowerProfileUpdate::Init(status, tx))),
+                State::Waiting(backend, rx, changes),
             )
         }
-        State::Waiting(conn, mut rx) => {
-            let power_proxy = match PowerDaemonProxy::new(&conn)
-                .await
-                .map_err(|e| e.to_string())
-            {
-                Ok(p) => p,
-                Err(e) => {
-                    return (
-                        Some((id, PowerProfileUpdate::Error(e))),
-                        State::Waiting(conn, rx),
-                    )
+        State::Waiting(backend, mut rx, mut changes) => {
+            let request = tokio::select! {
+                request = rx.recv() => request,
+                Some(_) = changes.next() => {
+                    let update = backend
+                        .status()
+                        .await
+                        .ok()
+                        .map(|status| (id, PowerProfileUpdate::Update(status)));
+                    return (update, State::Waiting(backend, rx, changes));
                 }
             };
-
-            match rx.recv().await {
+            match request {
                 Some(PowerProfileRequest::Get) => {
-                    if let Ok(profile) = get_power_profile(power_proxy).await {
+                    if let Ok(status) = backend.status().await {
                         (
-                            Some((id, PowerProfileUpdate::Update { profile })),
-                            State::Waiting(conn, rx),
+                            Some((id, PowerProfileUpdate::Update(status))),
+                            State::Waiting(backend, rx, changes),
                         )
                     } else {
-                        (None, State::Waiting(conn, rx))
+                        (None, State::Waiting(backend, rx, changes))
                     }
                 }
                 Some(PowerProfileRequest::Set(profile)) => {
-                    if set_power_profile(power_proxy, profile).await.is_ok() {
+                    if let Err(e) = backend.set_profile(profile).await {
+                        log::error!("Failed to set power profile: {}", e);
+                    }
+                    match backend.status().await {
+                        Ok(status) => (
+                            Some((id, PowerProfileUpdate::Update(status))),
+                            State::Waiting(backend, rx, changes),
+                        ),
+                        Err(_) => (None, State::Waiting(backend, rx, changes)),
+                    }
+                }
+                // only system76-power has charge thresholds
+                Some(PowerProfileRequest::GetChargeThresholds) => match &backend {
+                    Backend::System76(daemon) => (
+                        get_charge_thresholds(id, daemon).await,
+                        State::Waiting(backend, rx, changes),
+                    ),
+                    Backend::PowerProfiles(_) => (
+                        Some((id, PowerProfileUpdate::ChargeThresholdsUnsupported)),
+                        State::Waiting(backend, rx, changes),
+                    ),
+                },
+                Some(PowerProfileRequest::SetChargeThresholds(thresholds)) => match &backend {
+                    Backend::System76(daemon) => {
+                        // setting them needs authorization, so it may fail
+                        if let Err(e) = daemon.set_charge_thresholds(&thresholds).await {
+                            log::error!("Failed to set charge thresholds: {}", e);
+                        }
                         (
-                            Some((id, PowerProfileUpdate::Update { profile })),
-                            State::Waiting(conn, rx),
+                            get_charge_thresholds(id, daemon).await,
+                            State::Waiting(backend, rx, changes),
                         )
-                    } else {
-                        (None, State::Waiting(conn, rx))
                     }
-                }
+                    Backend::PowerProfiles(_) => (None, State::Waiting(backend, rx, changes)),
+                },
                 None => (None, State::Finished),
             }
         }
@@ -205,15 +424,43 @@ async fn start_listening<I: Copy>(id: I, state: State) -> (Option<(I, PowerProfi
     }
 }
 
+async fn get_charge_thresholds<I>(
+    id: I,
+    daemon: &PowerDaemonProxy<'_>,
+) -> Option<(I, PowerProfileUpdate)> {
+    let profiles = match get_charge_profiles(daemon).await {
+        Ok(profiles) => profiles,
+        Err(e) => return Some((id, PowerProfileUpdate::Error(e.to_string()))),
+    };
+    match daemon.get_charge_thresholds().await {
+        Ok(thresholds) => Some((
+            id,
+            PowerProfileUpdate::ChargeThresholds {
+                profiles,
+                thresholds,
+            },
+        )),
+        Err(e) => Some((id, PowerProfileUpdate::Error(e.to_string()))),
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum PowerProfileRequest {
     Get,
     Set(Power),
+    GetChargeThresholds,
+    SetChargeThresholds((u8, u8)),
 }
 
 #[derive(Debug, Clone)]
 pub enum PowerProfileUpdate {
-    Init(Power, UnboundedSender<PowerProfileRequest>),
-    Update { profile: Power },
+    Init(ProfileStatus, UnboundedSender<PowerProfileRequest>),
+    Update(ProfileStatus),
+    ChargeThresholds {
+        profiles: Vec<ChargeProfile>,
+        thresholds: (u8, u8),
+    },
+    /// the backend can't limit how far the battery is charged
+    ChargeThresholdsUnsupported,
     Error(String),
 }
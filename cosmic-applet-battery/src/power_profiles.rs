@@ -0,0 +1,41 @@
+//! # DBus interface proxy for: `net.hadess.PowerProfiles`
+//!
+//! The interface of power-profiles-daemon, used when system76-power isn't running.
+
+use std::collections::HashMap;
+use zbus::{dbus_proxy, zvariant::OwnedValue};
+
+#[dbus_proxy(
+    default_service = "net.hadess.PowerProfiles",
+    interface = "net.hadess.PowerProfiles",
+    default_path = "/net/hadess/PowerProfiles"
+)]
+trait PowerProfiles {
+    /// HoldProfile method
+    fn hold_profile(&self, profile: &str, reason: &str, application_id: &str) -> zbus::Result<u32>;
+
+    /// ReleaseProfile method
+    fn release_profile(&self, cookie: u32) -> zbus::Result<()>;
+
+    /// ProfileReleased signal
+    #[dbus_proxy(signal)]
+    fn profile_released(&self, cookie: u32) -> zbus::Result<()>;
+
+    /// ActiveProfile property
+    #[dbus_proxy(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn set_active_profile(&self, value: &str) -> zbus::Result<()>;
+
+    /// ActiveProfileHolds property
+    #[dbus_proxy(property)]
+    fn active_profile_holds(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+
+    /// PerformanceDegraded property
+    #[dbus_proxy(property)]
+    fn performance_degraded(&self) -> zbus::Result<String>;
+
+    /// Profiles property
+    #[dbus_proxy(property)]
+    fn profiles(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+}
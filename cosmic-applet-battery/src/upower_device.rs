@@ -159,74 +159,122 @@ pub fn device_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
 #[derive(Debug)]
 pub enum State {
     Ready,
-    Waiting(DeviceProxy<'static>),
+    Waiting(DeviceProxy<'static>, UPowerProxy<'static>, String),
     Finished,
 }
 
-async fn display_device() -> zbus::Result<DeviceProxy<'static>> {
+async fn display_device() -> zbus::Result<(DeviceProxy<'static>, UPowerProxy<'static>)> {
     let connection = zbus::Connection::system().await?;
     let upower = UPowerProxy::new(&connection).await?;
     let device_path = upower.get_display_device().await?;
-    DeviceProxy::builder(&connection)
+    let device = DeviceProxy::builder(&connection)
         .path(device_path)?
         .cache_properties(zbus::CacheProperties::Yes)
         .build()
+        .await?;
+    Ok((device, upower))
+}
+
+/// Health and recent history of the battery powering the computer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatteryDetails {
+    /// full capacity relative to the design capacity, in percent
+    pub health: Option<f64>,
+    pub charge_cycles: Option<u32>,
+    /// power draw in W
+    pub energy_rate: f64,
+    /// charge samples in percent, oldest first
+    pub charge_history: Vec<f64>,
+    /// power draw samples in W, oldest first
+    pub rate_history: Vec<f64>,
+}
+
+const HISTORY_TIMESPAN: u32 = 24 * 60 * 60;
+const HISTORY_RESOLUTION: u32 = 48;
+
+// The display device is a composite which has no history, so use the first real battery
+async fn system_battery(connection: &zbus::Connection) -> zbus::Result<DeviceProxy<'static>> {
+    let upower = UPowerProxy::new(connection).await?;
+    for path in upower.enumerate_devices().await? {
+        let device = DeviceProxy::builder(connection)
+            .path(path)?
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await?;
+        // type 2 is a battery
+        if device.type_().await? == 2 && device.power_supply().await? {
+            return Ok(device);
+        }
+    }
+    Err(zbus::Error::Failure("No battery found".to_string()))
+}
+
+async fn history(device: &DeviceProxy<'_>, type_: &str) -> Vec<f64> {
+    let mut history = device
+        .get_history(type_, HISTORY_TIMESPAN, HISTORY_RESOLUTION)
         .await
+        .unwrap_or_default();
+    // the order of the samples is not specified
+    history.sort_by_key(|(time, _, _)| *time);
+    history.into_iter().map(|(_, value, _)| value).collect()
+}
+
+pub async fn battery_details() -> zbus::Result<BatteryDetails> {
+    let connection = zbus::Connection::system().await?;
+    let device = system_battery(&connection).await?;
+    let energy_full = device.energy_full().await?;
+    let energy_full_design = device.energy_full_design().await?;
+    let has_history = device.has_history().await.unwrap_or_default();
+    Ok(BatteryDetails {
+        health: (energy_full_design > 0.0)
+            .then(|| (energy_full / energy_full_design * 100.0).min(100.0)),
+        charge_cycles: device
+            .charge_cycles()
+            .await
+            .ok()
+            .and_then(|c| u32::try_from(c).ok())
+            .filter(|c| *c > 0),
+        energy_rate: device.energy_rate().await?,
+        charge_history: if has_history {
+            history(&device, "charge").await
+        } else {
+            Vec::new()
+        },
+        rate_history: if has_history {
+            history(&device, "rate").await
+        } else {
+            Vec::new()
+        },
+    })
 }
 
 async fn start_listening<I: Copy>(id: I, state: State) -> (Option<(I, DeviceDbusEvent)>, State) {
     match state {
         State::Ready => {
-            if let Ok(device) = display_device().await {
+            if let Ok((device, upower)) = display_device().await {
+                let critical_action = upower.get_critical_action().await.unwrap_or_default();
                 return (
-                    Some((
-                        id,
-                        DeviceDbusEvent::Update {
-                            icon_name: device
-                                .cached_icon_name()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                            percent: device
-                                .cached_percentage()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                            time_to_empty: device
-                                .cached_time_to_empty()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                        },
-                    )),
-                    State::Waiting(device),
+                    Some((id, update(&device, &upower, &critical_action))),
+                    State::Waiting(device, upower, critical_action),
                 );
             }
             (None, State::Finished)
         }
-        State::Waiting(device) => {
+        State::Waiting(device, upower, critical_action) => {
             let mut stream = futures::stream_select!(
                 device.receive_icon_name_changed().await.map(|_| ()),
                 device.receive_percentage_changed().await.map(|_| ()),
                 device.receive_time_to_empty_changed().await.map(|_| ()),
+                device.receive_warning_level_changed().await.map(|_| ()),
+                device.receive_state_changed().await.map(|_| ()),
+                device.receive_time_to_full_changed().await.map(|_| ()),
+                device.receive_energy_rate_changed().await.map(|_| ()),
+                upower.receive_on_battery_changed().await.map(|_| ()),
             );
             match stream.next().await {
                 Some(_) => (
-                    Some((
-                        id,
-                        DeviceDbusEvent::Update {
-                            icon_name: device
-                                .cached_icon_name()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                            percent: device
-                                .cached_percentage()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                            time_to_empty: device
-                                .cached_time_to_empty()
-                                .unwrap_or_default()
-                                .unwrap_or_default(),
-                        },
-                    )),
-                    State::Waiting(device),
+                    Some((id, update(&device, &upower, &critical_action))),
+                    State::Waiting(device, upower, critical_action),
                 ),
                 None => (None, State::Finished),
             }
@@ -235,11 +283,109 @@ async fn start_listening<I: Copy>(id: I, state: State) -> (Option<(I, DeviceDbus
     }
 }
 
+fn update(device: &DeviceProxy, upower: &UPowerProxy, critical_action: &str) -> DeviceDbusEvent {
+    DeviceDbusEvent::Update {
+        icon_name: device
+            .cached_icon_name()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        percent: device
+            .cached_percentage()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        time_to_empty: device
+            .cached_time_to_empty()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        time_to_full: device
+            .cached_time_to_full()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        energy_rate: device
+            .cached_energy_rate()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        state: BatteryState::from(
+            device
+                .cached_state()
+                .unwrap_or_default()
+                .unwrap_or_default(),
+        ),
+        warning_level: WarningLevel::from(
+            device
+                .cached_warning_level()
+                .unwrap_or_default()
+                .unwrap_or_default(),
+        ),
+        on_battery: upower
+            .cached_on_battery()
+            .unwrap_or_default()
+            .unwrap_or_default(),
+        critical_action: critical_action.to_string(),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum DeviceDbusEvent {
     Update {
         icon_name: String,
         percent: f64,
         time_to_empty: i64,
+        time_to_full: i64,
+        energy_rate: f64,
+        state: BatteryState,
+        warning_level: WarningLevel,
+        on_battery: bool,
+        critical_action: String,
     },
 }
+
+/// `State` of a UPower device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatteryState {
+    #[default]
+    Unknown,
+    Charging,
+    Discharging,
+    Empty,
+    FullyCharged,
+    /// plugged in, but not charging, like when a charge threshold is reached
+    PendingCharge,
+    PendingDischarge,
+}
+
+impl From<u32> for BatteryState {
+    fn from(state: u32) -> Self {
+        match state {
+            1 => BatteryState::Charging,
+            2 => BatteryState::Discharging,
+            3 => BatteryState::Empty,
+            4 => BatteryState::FullyCharged,
+            5 => BatteryState::PendingCharge,
+            6 => BatteryState::PendingDischarge,
+            _ => BatteryState::Unknown,
+        }
+    }
+}
+
+/// `WarningLevel` of a UPower device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum WarningLevel {
+    #[default]
+    None,
+    Low,
+    Critical,
+    /// the critical action is about to be taken
+    Action,
+}
+
+impl From<u32> for WarningLevel {
+    fn from(level: u32) -> Self {
+        match level {
+            3 => WarningLevel::Low,
+            4 => WarningLevel::Critical,
+            5 => WarningLevel::Action,
+            _ => WarningLevel::None,
+        }
+    }
+}
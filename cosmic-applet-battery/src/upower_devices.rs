@@ -0,0 +1,271 @@
+//! # Power devices other than the display device
+//!
+//! Lists the UPower devices with a battery which doesn't power the computer, like
+//! wireless mice, keyboards, gamepads and headsets, and UPSes.
+
+use cosmic::iced::{self, subscription};
+use futures::{
+    stream::{abortable, AbortHandle, Abortable, BoxStream, SelectAll},
+    StreamExt,
+};
+use std::{fmt::Debug, hash::Hash};
+use zbus::{fdo, zvariant::OwnedObjectPath, CacheProperties, Connection};
+
+use crate::upower::{DeviceAddedStream, DeviceRemovedStream, UPowerProxy};
+use crate::upower_device::DeviceProxy;
+
+/// `Type` of a UPower device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    LinePower,
+    Battery,
+    Ups,
+    Mouse,
+    Keyboard,
+    Phone,
+    Tablet,
+    GamingInput,
+    Pen,
+    Touchpad,
+    Headset,
+    Headphones,
+    Other,
+}
+
+impl From<u32> for DeviceKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            1 => DeviceKind::LinePower,
+            2 => DeviceKind::Battery,
+            3 => DeviceKind::Ups,
+            5 => DeviceKind::Mouse,
+            6 => DeviceKind::Keyboard,
+            8 => DeviceKind::Phone,
+            10 => DeviceKind::Tablet,
+            12 => DeviceKind::GamingInput,
+            13 => DeviceKind::Pen,
+            14 => DeviceKind::Touchpad,
+            17 => DeviceKind::Headset,
+            19 => DeviceKind::Headphones,
+            _ => DeviceKind::Other,
+        }
+    }
+}
+
+impl DeviceKind {
+    pub fn icon_name(&self) -> Option<&'static str> {
+        match self {
+            DeviceKind::Ups => Some("uninterruptible-power-supply-symbolic"),
+            DeviceKind::Mouse => Some("input-mouse-symbolic"),
+            DeviceKind::Keyboard => Some("input-keyboard-symbolic"),
+            DeviceKind::Phone => Some("phone-symbolic"),
+            DeviceKind::Tablet | DeviceKind::Pen => Some("input-tablet-symbolic"),
+            DeviceKind::GamingInput => Some("input-gaming-symbolic"),
+            DeviceKind::Touchpad => Some("input-touchpad-symbolic"),
+            DeviceKind::Headset => Some("audio-headset-symbolic"),
+            DeviceKind::Headphones => Some("audio-headphones-symbolic"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerDevice {
+    pub kind: DeviceKind,
+    pub model: String,
+    pub icon_name: String,
+    pub percent: f64,
+}
+
+impl PowerDevice {
+    async fn new(device: &DeviceProxy<'_>) -> zbus::Result<Option<Self>> {
+        let kind = DeviceKind::from(device.type_().await?);
+        // the batteries powering the computer are shown by the display device
+        let peripheral = kind == DeviceKind::Ups || !device.power_supply().await?;
+        if kind == DeviceKind::LinePower || !peripheral || !device.is_present().await? {
+            return Ok(None);
+        }
+        Ok(Some(PowerDevice {
+            kind,
+            model: device.model().await.unwrap_or_default(),
+            icon_name: device.icon_name().await.unwrap_or_default(),
+            percent: device.percentage().await?,
+        }))
+    }
+
+    pub fn icon_name(&self) -> &str {
+        self.kind.icon_name().unwrap_or(&self.icon_name)
+    }
+}
+
+pub fn devices_subscription<I: 'static + Hash + Copy + Send + Sync + Debug>(
+    id: I,
+) -> iced::Subscription<(I, DevicesUpdate)> {
+    subscription::unfold(id, State::Ready, move |state| start_listening(id, state))
+}
+
+pub enum State {
+    Ready,
+    Waiting(Box<Watcher>),
+    Finished,
+}
+
+pub struct Watcher {
+    conn: Connection,
+    added: DeviceAddedStream<'static>,
+    removed: DeviceRemovedStream<'static>,
+    // yields the path of the device whose properties changed
+    property_changes: SelectAll<Abortable<BoxStream<'static, OwnedObjectPath>>>,
+    devices: Vec<WatchedDevice>,
+}
+
+// A UPower device and its last known state, which is `None` if it isn't shown
+struct WatchedDevice {
+    path: OwnedObjectPath,
+    proxy: DeviceProxy<'static>,
+    device: Option<PowerDevice>,
+    // ends the property changes stream of the device once it is removed
+    changes: AbortHandle,
+}
+
+impl WatchedDevice {
+    async fn new(
+        conn: &Connection,
+        path: OwnedObjectPath,
+    ) -> zbus::Result<(Self, Abortable<BoxStream<'static, OwnedObjectPath>>)> {
+        // properties are read when something changed, so they can't come from a stale cache
+        let proxy = DeviceProxy::builder(conn)
+            .path(path.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        let properties = fdo::PropertiesProxy::builder(conn)
+            .destination("org.freedesktop.UPower")?
+            .path(path.clone())?
+            .build()
+            .await?;
+        let changed = path.clone();
+        let (changes, handle) = abortable(
+            properties
+                .receive_properties_changed()
+                .await?
+                .map(move |_| changed.clone())
+                .boxed(),
+        );
+        let mut watched = WatchedDevice {
+            path,
+            proxy,
+            device: None,
+            changes: handle,
+        };
+        watched.refresh().await;
+        Ok((watched, changes))
+    }
+
+    async fn refresh(&mut self) {
+        self.device = match PowerDevice::new(&self.proxy).await {
+            Ok(device) => device,
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", self.path.as_str(), e);
+                None
+            }
+        };
+    }
+}
+
+impl Watcher {
+    async fn new(conn: Connection) -> zbus::Result<Self> {
+        let upower = UPowerProxy::new(&conn).await?;
+        let mut watcher = Watcher {
+            added: upower.receive_device_added().await?,
+            removed: upower.receive_device_removed().await?,
+            conn,
+            property_changes: SelectAll::new(),
+            devices: Vec::new(),
+        };
+        for path in upower.enumerate_devices().await? {
+            watcher.add_device(path).await;
+        }
+        Ok(watcher)
+    }
+
+    async fn add_device(&mut self, path: OwnedObjectPath) {
+        self.remove_device(path.as_str());
+        match WatchedDevice::new(&self.conn, path.clone()).await {
+            Ok((device, changes)) => {
+                self.property_changes.push(changes);
+                self.devices.push(device);
+            }
+            Err(e) => log::warn!("Failed to watch {}: {}", path.as_str(), e),
+        }
+    }
+
+    fn remove_device(&mut self, path: &str) {
+        self.devices.retain(|device| {
+            if device.path.as_str() == path {
+                device.changes.abort();
+            }
+            device.path.as_str() != path
+        });
+    }
+
+    fn devices(&self) -> Vec<PowerDevice> {
+        self.devices
+            .iter()
+            .filter_map(|device| device.device.clone())
+            .collect()
+    }
+}
+
+async fn start_listening<I: Copy>(id: I, state: State) -> (Option<(I, DevicesUpdate)>, State) {
+    match state {
+        State::Ready => {
+            let watcher = match Connection::system().await {
+                Ok(conn) => Watcher::new(conn).await,
+                Err(e) => Err(e),
+            };
+            match watcher {
+                Ok(watcher) => (
+                    Some((id, DevicesUpdate::Devices(watcher.devices()))),
+                    State::Waiting(Box::new(watcher)),
+                ),
+                Err(e) => (
+                    Some((id, DevicesUpdate::Error(e.to_string()))),
+                    State::Finished,
+                ),
+            }
+        }
+        State::Waiting(mut watcher) => {
+            tokio::select! {
+                Some(added) = watcher.added.next() => {
+                    if let Ok(args) = added.args() {
+                        watcher.add_device(args.device().to_owned().into()).await;
+                    }
+                }
+                Some(removed) = watcher.removed.next() => {
+                    if let Ok(args) = removed.args() {
+                        watcher.remove_device(args.device().as_str());
+                    }
+                }
+                Some(path) = watcher.property_changes.next() => {
+                    match watcher.devices.iter_mut().find(|device| device.path == path) {
+                        Some(device) => device.refresh().await,
+                        None => return (None, State::Waiting(watcher)),
+                    }
+                }
+                else => return (None, State::Finished),
+            };
+            (
+                Some((id, DevicesUpdate::Devices(watcher.devices()))),
+                State::Waiting(watcher),
+            )
+        }
+        State::Finished => iced::futures::future::pending().await,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DevicesUpdate {
+    Devices(Vec<PowerDevice>),
+    Error(String),
+}
@@ -4,6 +4,7 @@
 //! Source: `Interface '/org/freedesktop/UPower/KbdBacklight' from service 'org.freedesktop.UPower' on system bus`.
 
 use cosmic::iced;
+use futures::StreamExt;
 use iced::subscription;
 use std::{fmt::Debug, hash::Hash};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
@@ -38,11 +39,12 @@ pub fn kbd_backlight_subscription<I: 'static + Hash + Copy + Send + Sync + Debug
     subscription::unfold(id, State::Ready, move |state| start_listening(id, state))
 }
 
-#[derive(Debug)]
 pub enum State {
     Ready,
     Waiting(
         KbdBacklightProxy<'static>,
+        i32,
+        BrightnessChangedWithSourceStream<'static>,
         UnboundedReceiver<KeyboardBacklightRequest>,
     ),
     Finished,
@@ -62,37 +64,63 @@ async fn start_listening<I: Copy>(
                 Ok(p) => p,
                 Err(_) => return (None, State::Finished),
             };
+            let changes = match kbd_proxy.receive_brightness_changed_with_source().await {
+                Ok(s) => s,
+                Err(_) => return (None, State::Finished),
+            };
+            // the number of steps, most keyboards only have a few
+            let max_brightness = match kbd_proxy.get_max_brightness().await {
+                Ok(max_brightness) if max_brightness > 0 => max_brightness,
+                _ => return (None, State::Finished),
+            };
             let (tx, rx) = unbounded_channel();
 
-            let b = kbd_proxy.get_brightness().await.unwrap_or_default() as f64
-                / kbd_proxy.get_max_brightness().await.unwrap_or(1) as f64;
+            let b =
+                kbd_proxy.get_brightness().await.unwrap_or_default() as f64 / max_brightness as f64;
             (
-                Some((id, KeyboardBacklightUpdate::Init(tx, b))),
-                State::Waiting(kbd_proxy, rx),
+                Some((id, KeyboardBacklightUpdate::Init(tx, b, max_brightness))),
+                State::Waiting(kbd_proxy, max_brightness, changes, rx),
             )
         }
-        State::Waiting(proxy, mut rx) => match rx.recv().await {
-            Some(req) => match req {
-                KeyboardBacklightRequest::Get => {
-                    let b = proxy.get_brightness().await.unwrap_or_default() as f64
-                        / proxy.get_max_brightness().await.unwrap_or(1) as f64;
-                    (
-                        Some((id, KeyboardBacklightUpdate::Update(b))),
-                        State::Waiting(proxy, rx),
-                    )
-                }
-                KeyboardBacklightRequest::Set(value) => {
-                    if let Ok(max_brightness) = proxy.get_max_brightness().await {
+        State::Waiting(proxy, max_brightness, mut changes, mut rx) => {
+            tokio::select! {
+                req = rx.recv() => match req {
+                    Some(KeyboardBacklightRequest::Get) => {
+                        let b = proxy.get_brightness().await.unwrap_or_default() as f64
+                            / max_brightness as f64;
+                        (
+                            Some((id, KeyboardBacklightUpdate::Update(b))),
+                            State::Waiting(proxy, max_brightness, changes, rx),
+                        )
+                    }
+                    Some(KeyboardBacklightRequest::Set(value)) => {
                         let value = value.clamp(0., 1.) * (max_brightness as f64);
                         let value = value.round() as i32;
                         let _ = proxy.set_brightness(value).await;
-                    }
 
-                    (None, State::Waiting(proxy, rx))
+                        (None, State::Waiting(proxy, max_brightness, changes, rx))
+                    }
+                    None => (None, State::Finished),
+                },
+                Some(signal) = changes.next() => {
+                    let msg = match signal.args() {
+                        Ok(args) => {
+                            let b = *args.value() as f64 / max_brightness as f64;
+                            // "internal" for changes made by the firmware or the hardware keys,
+                            // "external" for those made over D-Bus, like ours, which don't
+                            // show the OSD
+                            if *args.source() == "internal" {
+                                Some((id, KeyboardBacklightUpdate::Changed(b)))
+                            } else {
+                                Some((id, KeyboardBacklightUpdate::Update(b)))
+                            }
+                        }
+                        Err(_) => None,
+                    };
+                    (msg, State::Waiting(proxy, max_brightness, changes, rx))
                 }
-            },
-            None => (None, State::Finished),
-        },
+            }
+        }
         State::Finished => iced::futures::future::pending().await,
     }
 }
@@ -100,7 +128,10 @@ async fn start_listening<I: Copy>(
 #[derive(Debug, Clone)]
 pub enum KeyboardBacklightUpdate {
     Update(f64),
-    Init(UnboundedSender<KeyboardBacklightRequest>, f64),
+    /// Brightness changed outside of the applet
+    Changed(f64),
+    /// With the brightness and the number of steps
+    Init(UnboundedSender<KeyboardBacklightRequest>, f64, i32),
 }
 
 #[derive(Debug, Clone)]
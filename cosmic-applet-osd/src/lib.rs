@@ -0,0 +1,98 @@
+//! On screen display of a level, shown on a layer surface while the popup of an
+//! applet is closed. Shared by the applets which change levels, like volume and
+//! brightness.
+
+use cosmic::iced::wayland::layer_surface::{destroy_layer_surface, get_layer_surface};
+use cosmic::iced::{
+    widget::{progress_bar, row, text},
+    window, Alignment, Command, Length,
+};
+use cosmic::iced_native::command::platform_specific::wayland::layer_surface::{
+    IcedMargin, IcedOutput, SctkLayerSurfaceSettings,
+};
+use cosmic::theme::Svg;
+use cosmic::widget::icon;
+use cosmic::Element;
+use sctk::shell::layer::{Anchor, KeyboardInteractivity, Layer};
+use std::time::Duration;
+
+const OSD_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default)]
+pub struct Osd {
+    surface: Option<window::Id>,
+    // incremented each time the OSD is shown, so only the last timeout hides it
+    generation: u32,
+}
+
+impl Osd {
+    /// Show the OSD, or keep it up if it is already shown, and schedule hiding it
+    pub fn show<M: 'static>(&mut self, id_ctr: &mut u32, hide: fn(u32) -> M) -> Command<M> {
+        self.generation = self.generation.wrapping_add(1);
+        let generation = self.generation;
+        let timeout = Command::perform(
+            async move {
+                tokio::time::sleep(OSD_TIMEOUT).await;
+                generation
+            },
+            hide,
+        );
+        if self.surface.is_some() {
+            return timeout;
+        }
+
+        *id_ctr += 1;
+        let id = window::Id::new(*id_ctr);
+        self.surface = Some(id);
+        Command::batch(vec![
+            get_layer_surface(SctkLayerSurfaceSettings {
+                id,
+                layer: Layer::Overlay,
+                keyboard_interactivity: KeyboardInteractivity::None,
+                anchor: Anchor::BOTTOM,
+                output: IcedOutput::Active,
+                namespace: "osd".to_string(),
+                margin: IcedMargin {
+                    top: 0,
+                    right: 0,
+                    bottom: 64,
+                    left: 0,
+                },
+                size: Some((Some(300), Some(64))),
+                exclusive_zone: 0,
+                ..Default::default()
+            }),
+            timeout,
+        ])
+    }
+
+    pub fn hide<M>(&mut self, generation: u32) -> Command<M> {
+        match self.surface {
+            Some(id) if generation == self.generation => {
+                self.surface = None;
+                destroy_layer_surface(id)
+            }
+            _ => Command::none(),
+        }
+    }
+}
+
+/// Content of the OSD, `level` and `max` are in percent
+pub fn view<'a, M: 'a>(icon_name: &'a str, level: f64, max: f64) -> Element<'a, M> {
+    row![
+        icon(icon_name, 24)
+            .width(Length::Units(24))
+            .height(Length::Units(24))
+            .style(Svg::Symbolic),
+        progress_bar(0.0..=max as f32, level as f32)
+            .height(Length::Units(8))
+            .width(Length::Fill),
+        text(format!("{:.0}%", level))
+            .width(Length::Units(40))
+            .size(14)
+    ]
+    .spacing(12)
+    .padding([12, 24])
+    .align_items(Alignment::Center)
+    .into()
+}
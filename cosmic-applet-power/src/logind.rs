@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+//! # DBus interface proxy for the inhibitor locks of: `org.freedesktop.login1.Manager`
+//!
+//! `Inhibit` takes a colon separated list of what to inhibit, like `idle:sleep`.
+
+use zbus::dbus_proxy;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait InhibitManager {
+    /// Inhibit method, the lock is held until the returned fd is closed
+    fn inhibit(
+        &self,
+        what: &str,
+        who: &str,
+        why: &str,
+        mode: &str,
+    ) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
+    /// ListInhibitors method
+    fn list_inhibitors(&self) -> zbus::Result<Vec<(String, String, String, String, u32, u32)>>;
+}
+
+/// An inhibitor lock taken by an application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inhibitor {
+    pub what: String,
+    pub who: String,
+    pub why: String,
+    pub mode: String,
+}
+
+impl Inhibitor {
+    /// Whether `what` (e.g. `sleep` or `shutdown`) is blocked rather than only delayed
+    pub fn blocks(&self, what: &str) -> bool {
+        self.mode == "block" && self.what.split(':').any(|w| w == what)
+    }
+}
+
+impl From<(String, String, String, String, u32, u32)> for Inhibitor {
+    fn from(
+        (what, who, why, mode, _uid, _pid): (String, String, String, String, u32, u32),
+    ) -> Self {
+        Inhibitor {
+            what,
+            who,
+            why,
+            mode,
+        }
+    }
+}
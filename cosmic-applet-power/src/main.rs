@@ -1,4 +1,6 @@
 use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use cosmic::applet::{CosmicAppletHelper, APPLET_BUTTON_THEME};
 use cosmic::iced::wayland::popup::{destroy_popup, get_popup};
@@ -9,7 +11,8 @@ use cosmic::widget::{button, divider, icon};
 use cosmic::Renderer;
 
 use cosmic::iced::{
-    widget::{self, column, container, row, Row},
+    time,
+    widget::{self, column, container, row, text, Column, Row},
     window, Alignment, Application, Command, Length, Subscription,
 };
 use cosmic::iced_style::application::{self, Appearance};
@@ -21,12 +24,15 @@ use logind_zbus::manager::ManagerProxy;
 use logind_zbus::session::{SessionProxy, SessionType};
 use logind_zbus::user::UserProxy;
 use nix::unistd::getuid;
+use zbus::zvariant::OwnedFd;
 use zbus::Connection;
 
 pub mod cosmic_session;
+pub mod logind;
 pub mod session_manager;
 
 use crate::cosmic_session::CosmicSessionProxy;
+use crate::logind::{InhibitManagerProxy, Inhibitor};
 use crate::session_manager::SessionManagerProxy;
 
 pub fn main() -> cosmic::iced::Result {
@@ -34,6 +40,46 @@ pub fn main() -> cosmic::iced::Result {
     Power::run(helper.window_settings())
 }
 
+// Shown in the panel while the system is kept awake, installed with the applet
+const KEEP_AWAKE_ICON: &str = "com.system76.CosmicAppletPower-keep-awake-symbolic";
+const INHIBIT_WHO: &str = "COSMIC Power Applet";
+// Time until a confirmed action proceeds on its own
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Actions ending the session, which are confirmed first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PowerAction {
+    LogOut,
+    Restart,
+    Shutdown,
+}
+
+impl PowerAction {
+    fn title(self) -> &'static str {
+        match self {
+            PowerAction::LogOut => "Log Out",
+            PowerAction::Restart => "Restart",
+            PowerAction::Shutdown => "Shutdown",
+        }
+    }
+
+    fn countdown(self, seconds: u64) -> String {
+        match self {
+            PowerAction::LogOut => format!("You will be logged out in {seconds} seconds."),
+            PowerAction::Restart => format!("The system will restart in {seconds} seconds."),
+            PowerAction::Shutdown => format!("The system will shut down in {seconds} seconds."),
+        }
+    }
+
+    fn perform(self) -> Command<Message> {
+        match self {
+            PowerAction::LogOut => Command::perform(log_out(), Message::Zbus),
+            PowerAction::Restart => Command::perform(restart(), Message::Zbus),
+            PowerAction::Shutdown => Command::perform(shutdown(), Message::Zbus),
+        }
+    }
+}
+
 #[derive(Default)]
 struct Power {
     applet_helper: CosmicAppletHelper,
@@ -41,6 +87,12 @@ struct Power {
     theme: Theme,
     popup: Option<window::Id>,
     id_ctr: u32,
+    // logind releases the lock when the fd is closed
+    keep_awake: Option<Arc<OwnedFd>>,
+    keep_awake_until: Option<Instant>,
+    inhibitors: Vec<Inhibitor>,
+    // the action waiting for confirmation, and when it proceeds
+    confirm: Option<(PowerAction, Instant)>,
 }
 
 #[derive(Debug, Clone)]
@@ -51,11 +103,121 @@ enum Message {
     Restart,
     Shutdown,
     TogglePopup,
+    PopupClosed(window::Id),
     Settings,
+    Confirm,
+    Cancel,
+    KeepAwake(Option<Duration>),
+    KeptAwake(Option<Duration>, Result<Arc<OwnedFd>, zbus::Error>),
+    StopKeepingAwake,
+    Inhibitors(Vec<Inhibitor>),
+    Tick,
     Ignore,
     Zbus(Result<(), zbus::Error>),
 }
 
+impl Power {
+    fn keep_awake_remaining(&self) -> String {
+        match self.keep_awake_until {
+            Some(until) => {
+                let remaining = until.saturating_duration_since(Instant::now());
+                format!("{} min left", (remaining.as_secs_f64() / 60.0).ceil())
+            }
+            None => "Until disabled".to_string(),
+        }
+    }
+
+    fn keep_awake_view(&self) -> Element<Message> {
+        if self.keep_awake.is_some() {
+            row![
+                text_icon(KEEP_AWAKE_ICON, 24),
+                text("Keeping Awake").width(Length::Fill),
+                text(self.keep_awake_remaining()),
+                widget::button(text("Turn Off"))
+                    .style(theme::Button::Text)
+                    .on_press(Message::StopKeepingAwake),
+            ]
+            .spacing(8)
+            .padding([0, 24])
+            .align_items(Alignment::Center)
+            .into()
+        } else {
+            row![
+                text_icon(KEEP_AWAKE_ICON, 24),
+                text("Keep Awake").width(Length::Fill),
+                duration_button("30 min", Some(Duration::from_secs(30 * 60))),
+                duration_button("1 hour", Some(Duration::from_secs(60 * 60))),
+                duration_button("Until disabled", None),
+            ]
+            .spacing(8)
+            .padding([0, 24])
+            .align_items(Alignment::Center)
+            .into()
+        }
+    }
+
+    // Other applications holding a lock on any of `what`
+    fn inhibitors_view(&self, what: &[&str], heading: &str) -> Option<Element<Message>> {
+        let blocking: Vec<_> = self
+            .inhibitors
+            .iter()
+            .filter(|i| i.who != INHIBIT_WHO && what.iter().any(|w| i.blocks(w)))
+            .collect();
+        if blocking.is_empty() {
+            return None;
+        }
+        let mut list = Column::new().push(text(heading).size(14)).spacing(4);
+        for inhibitor in blocking {
+            list = list.push(text(format!("{}: {}", inhibitor.who, inhibitor.why)).size(14));
+        }
+        Some(list.into())
+    }
+
+    fn confirm_view(&self, action: PowerAction, deadline: Instant) -> Element<Message> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        // rounded up, so it only shows 0 once the action is taken
+        let seconds = remaining.as_secs_f64().ceil() as u64;
+        let mut content = column![
+            text(format!("{}?", action.title()))
+                .size(20)
+                .width(Length::Fill),
+            text(action.countdown(seconds)).width(Length::Fill),
+        ]
+        .spacing(12)
+        .padding([8, 24]);
+        // logind has no lock for logging out
+        if action != PowerAction::LogOut {
+            if let Some(inhibitors) =
+                self.inhibitors_view(&["shutdown"], "Applications blocking shutdown:")
+            {
+                content = content.push(inhibitors);
+            }
+        }
+        content
+            .push(
+                row![
+                    button(theme::Button::Secondary)
+                        .custom(vec![text("Cancel").width(Length::Fill).into()])
+                        .padding([8, 24])
+                        .on_press(Message::Cancel)
+                        .width(Length::Fill),
+                    button(theme::Button::Destructive)
+                        .custom(vec![text(action.title()).width(Length::Fill).into()])
+                        .padding([8, 24])
+                        .on_press(Message::Confirm)
+                        .width(Length::Fill),
+                ]
+                .spacing(12),
+            )
+            .into()
+    }
+
+    fn ask_confirmation(&mut self, action: PowerAction) -> Command<Message> {
+        self.confirm = Some((action, Instant::now() + CONFIRM_TIMEOUT));
+        Command::perform(list_inhibitors(), Message::Inhibitors)
+    }
+}
+
 impl Application for Power {
     type Message = Message;
     type Theme = Theme;
@@ -80,8 +242,11 @@ impl Application for Power {
         self.theme
     }
 
-    fn close_requested(&self, _id: SurfaceIdWrapper) -> Self::Message {
-        Message::Ignore
+    fn close_requested(&self, id: SurfaceIdWrapper) -> Self::Message {
+        match id {
+            SurfaceIdWrapper::LayerSurface(_) | SurfaceIdWrapper::Window(_) => Message::Ignore,
+            SurfaceIdWrapper::Popup(id) => Message::PopupClosed(id),
+        }
     }
 
     fn style(&self) -> <Self::Theme as application::StyleSheet>::Style {
@@ -92,13 +257,21 @@ impl Application for Power {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        Subscription::none()
+        // update the remaining times and act once they're over, the
+        // confirmation only counts down while its popup is shown
+        if self.keep_awake_until.is_some() || (self.popup.is_some() && self.confirm.is_some()) {
+            time::every(Duration::from_secs(1)).map(|_| Message::Tick)
+        } else {
+            Subscription::none()
+        }
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::TogglePopup => {
                 if let Some(p) = self.popup.take() {
+                    // closing the popup cancels the pending action
+                    self.confirm = None;
                     destroy_popup(p)
                 } else {
                     self.id_ctr += 1;
@@ -117,18 +290,80 @@ impl Application for Power {
                         .min_height(100)
                         .max_height(400)
                         .max_width(500);
-                    get_popup(popup_settings)
+                    Command::batch(vec![
+                        get_popup(popup_settings),
+                        Command::perform(list_inhibitors(), Message::Inhibitors),
+                    ])
+                }
+            }
+            Message::PopupClosed(id) => {
+                if self.popup.as_ref() == Some(&id) {
+                    // dismissing the popup cancels the pending action too
+                    self.popup = None;
+                    self.confirm = None;
                 }
+                Command::none()
             }
             Message::Settings => {
                 let _ = process::Command::new("cosmic-settings").spawn();
                 Command::none()
             }
             Message::Lock => Command::perform(lock(), Message::Zbus),
-            Message::LogOut => Command::perform(log_out(), Message::Zbus),
+            Message::LogOut => self.ask_confirmation(PowerAction::LogOut),
             Message::Suspend => Command::perform(suspend(), Message::Zbus),
-            Message::Restart => Command::perform(restart(), Message::Zbus),
-            Message::Shutdown => Command::perform(shutdown(), Message::Zbus),
+            Message::Restart => self.ask_confirmation(PowerAction::Restart),
+            Message::Shutdown => self.ask_confirmation(PowerAction::Shutdown),
+            Message::Confirm => match self.confirm.take() {
+                Some((action, _)) => action.perform(),
+                None => Command::none(),
+            },
+            Message::Cancel => {
+                self.confirm = None;
+                Command::none()
+            }
+            Message::KeepAwake(duration) => Command::perform(keep_awake(), move |result| {
+                Message::KeptAwake(duration, result)
+            }),
+            Message::KeptAwake(duration, result) => match result {
+                Ok(fd) => {
+                    self.keep_awake = Some(fd);
+                    self.keep_awake_until = duration.map(|d| Instant::now() + d);
+                    Command::perform(list_inhibitors(), Message::Inhibitors)
+                }
+                Err(e) => {
+                    eprintln!("cosmic-applet-power ERROR: '{}'", e);
+                    Command::none()
+                }
+            },
+            Message::StopKeepingAwake => {
+                self.keep_awake = None;
+                self.keep_awake_until = None;
+                Command::perform(list_inhibitors(), Message::Inhibitors)
+            }
+            Message::Inhibitors(inhibitors) => {
+                self.inhibitors = inhibitors;
+                Command::none()
+            }
+            Message::Tick => {
+                let now = Instant::now();
+                let mut commands = Vec::new();
+                if matches!(self.keep_awake_until, Some(until) if until <= now) {
+                    commands.push(self.update(Message::StopKeepingAwake));
+                }
+                if self.popup.is_some() {
+                    match self.confirm {
+                        Some((_, deadline)) if deadline <= now => {
+                            commands.push(self.update(Message::Confirm));
+                        }
+                        // show blockers appearing or going away before the action is taken
+                        Some(_) => {
+                            commands.push(Command::perform(list_inhibitors(), Message::Inhibitors));
+                        }
+                        None => {}
+                    }
+                }
+                Command::batch(commands)
+            }
             Message::Zbus(result) => {
                 if let Err(e) = result {
                     eprintln!("cosmic-applet-power ERROR: '{}'", e);
@@ -144,10 +379,21 @@ impl Application for Power {
             SurfaceIdWrapper::LayerSurface(_) => unimplemented!(),
             SurfaceIdWrapper::Window(_) => self
                 .applet_helper
-                .icon_button(&self.icon_name)
+                .icon_button(if self.keep_awake.is_some() {
+                    KEEP_AWAKE_ICON
+                } else {
+                    &self.icon_name
+                })
                 .on_press(Message::TogglePopup)
                 .into(),
             SurfaceIdWrapper::Popup(_) => {
+                if let Some((action, deadline)) = self.confirm {
+                    return self
+                        .applet_helper
+                        .popup_container(self.confirm_view(action, deadline))
+                        .into();
+                }
+
                 let settings = row_button(vec!["Settings...".into()]).on_press(Message::Settings);
 
                 let session = column![
@@ -177,8 +423,25 @@ impl Application for Power {
                 .spacing(24)
                 .padding([0, 24]);
 
+                // logind's idle lock is up to the compositor, which blanks the screen regardless
+                let mut keep_awake = column![
+                    self.keep_awake_view(),
+                    container(text("Prevents suspend. The screen may still turn off.").size(12))
+                        .padding([0, 24]),
+                ]
+                .spacing(8);
+                if let Some(inhibitors) =
+                    self.inhibitors_view(&["sleep", "idle"], "Blocking sleep:")
+                {
+                    keep_awake = keep_awake.push(container(inhibitors).padding([0, 24]));
+                }
+
                 let content = column![
                     settings,
+                    container(divider::horizontal::light())
+                        .padding([0, 12])
+                        .width(Length::Fill),
+                    keep_awake,
                     container(divider::horizontal::light())
                         .padding([0, 12])
                         .width(Length::Fill),
@@ -225,6 +488,12 @@ fn text_icon(name: &str, size: u16) -> cosmic::widget::Icon {
     icon(name, size).style(Svg::Symbolic)
 }
 
+fn duration_button(label: &str, duration: Option<Duration>) -> widget::Button<Message, Renderer> {
+    widget::button(text(label))
+        .style(theme::Button::Text)
+        .on_press(Message::KeepAwake(duration))
+}
+
 // ### System helpers
 
 async fn restart() -> zbus::Result<()> {
@@ -245,6 +514,37 @@ async fn suspend() -> zbus::Result<()> {
     manager_proxy.suspend(true).await
 }
 
+async fn keep_awake() -> zbus::Result<Arc<OwnedFd>> {
+    let connection = Connection::system().await?;
+    let manager_proxy = InhibitManagerProxy::new(&connection).await?;
+    let fd = manager_proxy
+        .inhibit(
+            "idle:sleep",
+            INHIBIT_WHO,
+            "Keep awake was turned on",
+            "block",
+        )
+        .await?;
+    Ok(Arc::new(fd))
+}
+
+async fn list_inhibitors() -> Vec<Inhibitor> {
+    let inhibitors = match Connection::system().await {
+        Ok(connection) => match InhibitManagerProxy::new(&connection).await {
+            Ok(manager_proxy) => manager_proxy.list_inhibitors().await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    match inhibitors {
+        Ok(inhibitors) => inhibitors.into_iter().map(Inhibitor::from).collect(),
+        Err(e) => {
+            eprintln!("cosmic-applet-power ERROR: '{}'", e);
+            Vec::new()
+        }
+    }
+}
+
 async fn lock() -> zbus::Result<()> {
     let connection = Connection::system().await?;
     let manager_proxy = ManagerProxy::new(&connection).await?;